@@ -1,13 +1,210 @@
+use std::borrow::Cow;
+
 use crate::document_type::DocumentType;
+use crate::renderer::{
+    escape_text, render_comment, render_conditional_comment, render_element,
+    render_raw_html, Markup, RenderOptions,
+};
 
+// https://developer.mozilla.org/en-US/docs/Web/API/Attr
 #[derive(Debug, Eq, PartialEq)]
 pub struct Attr<'a> {
     pub name: &'a str,
-    pub value: &'a str,
+    /// `None` for a value-less (boolean) attribute like `checked`
+    pub value: Option<Cow<'a, str>>,
+}
+
+// https://html.spec.whatwg.org/multipage/indices.html#attributes-3
+// (name, elements which have it, or empty for any element)
+pub const BOOLEAN_ATTRIBUTES: [(&str, &[&str]); 25] = [
+    ("allowfullscreen", &["iframe"]),
+    ("async", &["script"]),
+    ("autofocus", &[]),
+    ("autoplay", &["audio", "video"]),
+    ("checked", &["input"]),
+    ("controls", &["audio", "video"]),
+    ("default", &["track"]),
+    ("defer", &["script"]),
+    (
+        "disabled",
+        &[
+            "button", "fieldset", "input", "link", "optgroup", "option",
+            "select", "textarea",
+        ],
+    ),
+    ("formnovalidate", &["button", "input"]),
+    ("hidden", &[]),
+    ("inert", &[]),
+    ("ismap", &["img"]),
+    ("itemscope", &[]),
+    ("loop", &["audio", "video"]),
+    ("multiple", &["input", "select"]),
+    ("muted", &["audio", "video"]),
+    ("nomodule", &["script"]),
+    ("novalidate", &["form"]),
+    ("open", &["details", "dialog"]),
+    ("playsinline", &["video"]),
+    ("readonly", &["input", "textarea"]),
+    ("required", &["input", "select", "textarea"]),
+    ("reversed", &["ol"]),
+    ("selected", &["option"]),
+];
+
+impl<'a> Attr<'a> {
+    pub fn new<T>(name: &'a str, value: T) -> Self
+    where
+        T: Into<Cow<'a, str>>,
+    {
+        Self {
+            name,
+            value: Some(value.into()),
+        }
+    }
+
+    pub fn boolean(name: &'a str) -> Self {
+        Self { name, value: None }
+    }
 }
 
-pub type HTMLCollection<'a> = Vec<Element<'a>>;
 pub type NamedNodeMap<'a> = Vec<Attr<'a>>;
+pub type NodeList<'a> = Vec<Node<'a>>;
+
+// https://developer.mozilla.org/en-US/docs/Web/API/Node
+// NOTE:
+// The doctype is not a node. It can only be the first line of a template, so
+// it's kept in `Document.type` rather than in a node list.
+#[derive(Debug, Eq, PartialEq)]
+pub enum Node<'a> {
+    Element(Element<'a>),
+    Text(Text<'a>),
+    Comment(Comment<'a>),
+    ConditionalComment(ConditionalComment<'a>),
+    RawHtml(RawHtml<'a>),
+}
+
+impl<'a> Node<'a> {
+    pub fn as_element(&self) -> Option<&Element<'a>> {
+        match self {
+            Node::Element(e) => Some(e),
+            _ => None,
+        }
+    }
+
+    pub fn as_text(&self) -> Option<&Text<'a>> {
+        match self {
+            Node::Text(t) => Some(t),
+            _ => None,
+        }
+    }
+
+    pub fn as_comment(&self) -> Option<&Comment<'a>> {
+        match self {
+            Node::Comment(c) => Some(c),
+            _ => None,
+        }
+    }
+
+    pub fn as_conditional_comment(&self) -> Option<&ConditionalComment<'a>> {
+        match self {
+            Node::ConditionalComment(c) => Some(c),
+            _ => None,
+        }
+    }
+
+    pub fn as_raw_html(&self) -> Option<&RawHtml<'a>> {
+        match self {
+            Node::RawHtml(r) => Some(r),
+            _ => None,
+        }
+    }
+
+    pub fn as_tag(&self) -> Markup<'_> {
+        match self {
+            Node::Element(e) => e.as_tag(),
+            Node::Text(t) => t.as_tag(),
+            Node::Comment(c) => c.as_tag(),
+            Node::ConditionalComment(c) => c.as_tag(),
+            Node::RawHtml(r) => r.as_tag(),
+        }
+    }
+}
+
+// https://developer.mozilla.org/en-US/docs/Web/API/Text
+#[derive(Debug, Eq, PartialEq)]
+pub struct Text<'a> {
+    pub data: Cow<'a, str>,
+}
+
+impl<'a> Text<'a> {
+    pub fn new<T>(data: T) -> Self
+    where
+        T: Into<Cow<'a, str>>,
+    {
+        Self { data: data.into() }
+    }
+
+    /// Returns the text escaped as HTML.
+    pub fn as_tag(&self) -> Markup<'_> {
+        escape_text(self.data.as_ref())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CommentKind {
+    /// `/` (not rendered)
+    Code,
+    /// `/!`
+    Html,
+}
+
+// https://developer.mozilla.org/en-US/docs/Web/API/Comment
+#[derive(Debug, Eq, PartialEq)]
+pub struct Comment<'a> {
+    pub kind: CommentKind,
+    pub data: Cow<'a, str>,
+}
+
+impl<'a> Comment<'a> {
+    pub fn new<T>(kind: CommentKind, data: T) -> Self
+    where
+        T: Into<Cow<'a, str>>,
+    {
+        Self {
+            kind,
+            data: data.into(),
+        }
+    }
+
+    pub fn as_tag(&self) -> Markup<'static> {
+        render_comment(self, &RenderOptions::default())
+    }
+}
+
+// e.g. `<!--[if IE]>...<![endif]-->`
+#[derive(Debug, Eq, PartialEq)]
+pub struct ConditionalComment<'a> {
+    pub condition: &'a str,
+    pub children: NodeList<'a>,
+}
+
+impl<'a> ConditionalComment<'a> {
+    pub fn as_tag(&self) -> Markup<'static> {
+        render_conditional_comment(self, &RenderOptions::default())
+    }
+}
+
+// A line starting with `<` (written out verbatim)
+#[derive(Debug, Eq, PartialEq)]
+pub struct RawHtml<'a> {
+    pub data: &'a str,
+    pub children: NodeList<'a>,
+}
+
+impl<'a> RawHtml<'a> {
+    pub fn as_tag(&self) -> Markup<'static> {
+        render_raw_html(self, &RenderOptions::default())
+    }
+}
 
 // https://developer.mozilla.org/en-US/docs/Web/API/Element
 // https://rustwasm.github.io/wasm-bindgen/api/web_sys/struct.Element.html
@@ -15,7 +212,13 @@ pub type NamedNodeMap<'a> = Vec<Attr<'a>>;
 pub struct Element<'a> {
     pub name: String,
     pub attributes: NamedNodeMap<'a>,
-    pub children: HTMLCollection<'a>,
+    pub children: NodeList<'a>,
+    /// `<` adds a white space before the tag
+    pub leading_whitespace: bool,
+    /// `>` adds a white space after the tag
+    pub trailing_whitespace: bool,
+    /// `/` closes the tag explicitly even if it's not a void element
+    pub self_closing: bool,
 }
 
 // https://html.spec.whatwg.org/multipage/syntax.html#void-elements
@@ -24,38 +227,96 @@ const VOID_ELEMENTS: [&str; 13] = [
     "source", "track", "wbr",
 ];
 
+/// Kinds of elements by their content.
+///
+/// See https://html.spec.whatwg.org/multipage/syntax.html#elements-2
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ElementKind {
+    Void,
+    /// `script` and `style` (the text is never escaped)
+    RawText,
+    /// `textarea` and `pre` (the text is escaped)
+    ///
+    /// NOTE: `pre` is a normal element in the spec, but its body is taken as
+    /// text to keep the whitespace as it is.
+    EscapableRawText,
+    Normal,
+}
+
+// NOTE: Keep these in sync with `raw_text_tag_name` in grammar.pest.
+const RAW_TEXT_ELEMENTS: [&str; 2] = ["script", "style"];
+
+const ESCAPABLE_RAW_TEXT_ELEMENTS: [&str; 2] = ["pre", "textarea"];
+
+// elements which are kept on the same line in pretty output
+const INLINE_ELEMENTS: [&str; 27] = [
+    "a", "abbr", "acronym", "b", "bdo", "big", "br", "button", "cite", "code",
+    "dfn", "em", "i", "img", "input", "kbd", "label", "q", "samp", "select",
+    "small", "span", "strong", "sub", "sup", "tt", "var",
+];
+
 impl<'a> Element<'a> {
     pub fn new() -> Self {
         Self {
             name: "".to_string(),
             attributes: vec![],
             children: vec![],
+            leading_whitespace: false,
+            trailing_whitespace: false,
+            self_closing: false,
         }
     }
 
-    pub fn as_tag(&self) -> String {
-        let mut out = format!("<{}", self.name);
-        if !self.attributes.is_empty() {
-            out.push(' ');
-            out.push_str(
-                &self
-                    .attributes
-                    .iter()
-                    .map(|a| format!("{}=\"{}\"", a.name, a.value))
-                    .collect::<Vec<_>>()
-                    .join(" "),
-            );
-        }
-        if VOID_ELEMENTS.contains(&self.name.as_str()) {
-            out.push_str(" />");
+    /// Returns true if the element is one of the void elements.
+    pub fn is_void(&self) -> bool {
+        VOID_ELEMENTS.contains(&self.name.as_str())
+    }
+
+    /// Returns true if the name is a boolean attribute of the element (e.g.
+    /// `selected` of `option`, or `hidden` of any element).
+    pub fn has_boolean_attribute(&self, name: &str) -> bool {
+        BOOLEAN_ATTRIBUTES.iter().any(|(n, elements)| {
+            *n == name
+                && (elements.is_empty()
+                    || elements.contains(&self.name.as_str()))
+        })
+    }
+
+    pub fn kind(&self) -> ElementKind {
+        let name = self.name.as_str();
+        if VOID_ELEMENTS.contains(&name) {
+            ElementKind::Void
+        } else if RAW_TEXT_ELEMENTS.contains(&name) {
+            ElementKind::RawText
+        } else if ESCAPABLE_RAW_TEXT_ELEMENTS.contains(&name) {
+            ElementKind::EscapableRawText
         } else {
-            out.push('>');
-            for c in &self.children {
-                out.push_str(&c.as_tag());
-            }
-            out.push_str(&format!("</{}>", self.name));
+            ElementKind::Normal
         }
-        out
+    }
+
+    /// Returns true if the whitespace in the content is kept as it is (raw
+    /// text and escapable raw text elements).
+    pub fn is_preformatted(&self) -> bool {
+        matches!(
+            self.kind(),
+            ElementKind::RawText | ElementKind::EscapableRawText
+        )
+    }
+
+    /// Returns true if the element is rendered inline in pretty output.
+    pub fn is_inline(&self) -> bool {
+        INLINE_ELEMENTS.contains(&self.name.as_str())
+    }
+
+    /// Returns true if the element is a void element or it's closed
+    /// explicitly with a trailing `/`.
+    pub fn is_self_closing(&self) -> bool {
+        self.self_closing || self.is_void()
+    }
+
+    pub fn as_tag(&self) -> Markup<'static> {
+        render_element(self, &RenderOptions::default())
     }
 }
 
@@ -69,8 +330,9 @@ impl<'a> Default for Element<'a> {
 // https://rustwasm.github.io/wasm-bindgen/api/web_sys/struct.Document.html
 #[derive(Debug, Eq, PartialEq)]
 pub struct Document<'a> {
+    /// `doctype` on the first line, which is written before the children
     pub r#type: Option<DocumentType<'a>>,
-    pub children: Vec<Element<'a>>,
+    pub children: NodeList<'a>,
 }
 
 impl<'a> Document<'a> {
@@ -120,15 +382,240 @@ mod test {
         assert_eq!(a, b);
     }
 
+    #[test]
+    fn test_element_kind() {
+        let kinds = [
+            ("br", ElementKind::Void),
+            ("script", ElementKind::RawText),
+            ("style", ElementKind::RawText),
+            ("textarea", ElementKind::EscapableRawText),
+            ("pre", ElementKind::EscapableRawText),
+            ("title", ElementKind::Normal),
+            ("div", ElementKind::Normal),
+        ];
+        for (name, kind) in kinds.iter() {
+            let e = Element {
+                name: name.to_string(),
+                ..Default::default()
+            };
+            assert_eq!(*kind, e.kind());
+        }
+
+        for name in &["pre", "script", "textarea"] {
+            let e = Element {
+                name: name.to_string(),
+                ..Default::default()
+            };
+            assert!(e.is_preformatted());
+        }
+        let e = Element {
+            name: "code".to_string(),
+            ..Default::default()
+        };
+        assert!(!e.is_preformatted());
+        let e = Element {
+            name: "br".to_string(),
+            ..Default::default()
+        };
+        assert!(!e.is_preformatted());
+    }
+
+    #[test]
+    fn test_element_has_boolean_attribute() {
+        let option = Element {
+            name: "option".to_string(),
+            ..Default::default()
+        };
+        assert!(option.has_boolean_attribute("selected"));
+        assert!(option.has_boolean_attribute("hidden"));
+        assert!(!option.has_boolean_attribute("checked"));
+        assert!(!option.has_boolean_attribute("value"));
+
+        let p = Element {
+            name: "p".to_string(),
+            ..Default::default()
+        };
+        assert!(!p.has_boolean_attribute("required"));
+    }
+
+    #[test]
+    fn test_element_is_inline() {
+        for name in &["a", "span", "em", "img"] {
+            let e = Element {
+                name: name.to_string(),
+                ..Default::default()
+            };
+            assert!(e.is_inline());
+        }
+        for name in &["div", "p", "li", "pre"] {
+            let e = Element {
+                name: name.to_string(),
+                ..Default::default()
+            };
+            assert!(!e.is_inline());
+        }
+    }
+
+    #[test]
+    fn test_element_is_void() {
+        for name in VOID_ELEMENTS.iter() {
+            let e = Element {
+                name: name.to_string(),
+                ..Default::default()
+            };
+            assert!(e.is_void());
+        }
+
+        let e = Element {
+            name: "div".to_string(),
+            ..Default::default()
+        };
+        assert!(!e.is_void());
+    }
+
+    #[test]
+    fn test_element_is_self_closing() {
+        let e = Element {
+            name: "br".to_string(),
+            ..Default::default()
+        };
+        assert!(e.is_self_closing());
+
+        let e = Element {
+            name: "my-icon".to_string(),
+            self_closing: true,
+            ..Default::default()
+        };
+        assert!(e.is_self_closing());
+        assert!(!e.is_void());
+
+        let e = Element {
+            name: "my-icon".to_string(),
+            ..Default::default()
+        };
+        assert!(!e.is_self_closing());
+    }
+
+    #[test]
+    fn test_element_as_tag() {
+        let br = Element {
+            name: "br".to_string(),
+            ..Default::default()
+        };
+        assert_eq!("<br>", br.as_tag());
+
+        let img = Element {
+            name: "img".to_string(),
+            attributes: vec![Attr {
+                name: "src",
+                value: Some("logo.png".into()),
+            }],
+            children: vec![],
+            ..Default::default()
+        };
+        assert_eq!(r#"<img src="logo.png">"#, img.as_tag());
+
+        let p = Element {
+            name: "p".to_string(),
+            ..Default::default()
+        };
+        assert_eq!("<p></p>", p.as_tag());
+
+        let icon = Element {
+            name: "my-icon".to_string(),
+            attributes: vec![Attr::new("name", "star")],
+            self_closing: true,
+            ..Default::default()
+        };
+        assert_eq!(r#"<my-icon name="star" />"#, icon.as_tag());
+    }
+
+    #[test]
+    fn test_text_as_tag() {
+        let text = Text::new("Hello world");
+        assert_eq!("Hello world", text.as_tag());
+
+        let text = Text::new("<b>Tom & Jerry</b>");
+        assert_eq!("&lt;b&gt;Tom &amp; Jerry&lt;/b&gt;", text.as_tag());
+    }
+
+    #[test]
+    fn test_node_as_tag() {
+        let p = Element {
+            name: "p".to_string(),
+            attributes: vec![],
+            children: vec![Node::Text(Text::new("1 < 2"))],
+            ..Default::default()
+        };
+        let node = Node::Element(p);
+        assert!(node.as_element().is_some());
+        assert!(node.as_text().is_none());
+        assert_eq!("<p>1 &lt; 2</p>", node.as_tag());
+    }
+
+    #[test]
+    fn test_comment_as_tag() {
+        let comment = Comment::new(CommentKind::Code, "Foo");
+        assert_eq!("", comment.as_tag());
+
+        let node = Node::Comment(comment);
+        assert!(node.as_comment().is_some());
+        assert!(node.as_element().is_none());
+        assert_eq!("", node.as_tag());
+
+        let comment = Comment::new(CommentKind::Html, "Bar");
+        assert_eq!("<!--Bar-->", comment.as_tag());
+    }
+
+    #[test]
+    fn test_conditional_comment_as_tag() {
+        let comment = ConditionalComment {
+            condition: "if IE",
+            children: vec![Node::Element(Element {
+                name: "p".to_string(),
+                attributes: vec![],
+                children: vec![Node::Text(Text::new("Get a better browser."))],
+                ..Default::default()
+            })],
+        };
+        assert_eq!(
+            "<!--[if IE]><p>Get a better browser.</p><![endif]-->",
+            comment.as_tag()
+        );
+    }
+
+    #[test]
+    fn test_raw_html_as_tag() {
+        let raw = RawHtml {
+            data: r#"<div class="embed">"#,
+            children: vec![Node::Text(Text::new("a & b"))],
+        };
+        assert_eq!(r#"<div class="embed">a &amp; b"#, raw.as_tag());
+
+        let node = Node::RawHtml(raw);
+        assert!(node.as_raw_html().is_some());
+        assert!(node.as_element().is_none());
+    }
+
+    #[test]
+    fn test_attr_new() {
+        let attr = Attr::new("type", "checkbox");
+        assert_eq!(Some("checkbox"), attr.value.as_deref());
+
+        let attr = Attr::boolean("checked");
+        assert_eq!("checked", attr.name);
+        assert!(attr.value.is_none());
+    }
+
     #[test]
     fn test_attr_equality() {
         let a = Attr {
             name: "",
-            value: "",
+            value: Some("".into()),
         };
         let b = Attr {
             name: "",
-            value: "",
+            value: Some("".into()),
         };
         assert_eq!(a, b);
     }
@@ -1,46 +1,122 @@
-use crate::dtd::DTD;
+use std::borrow::Cow;
+use std::fmt;
+
+use anyhow::Error;
+
+use crate::dtd::{Spec, Variant, DTD};
+use crate::renderer::{Format, Markup};
 
 // https://developer.mozilla.org/en-US/docs/Web/API/DocumentType
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct DocumentType<'a> {
-    dtd: DTD<'a>,
+    pub spec: Spec,
+    /// `None` for an XML prolog and a raw declaration
+    pub variant: Option<Variant>,
     pub name: &'a str,
     pub public_id: &'static str,
     pub system_id: &'static str,
+    /// encoding in the XML prolog (only for `doctype xml`)
+    pub encoding: Option<&'a str>,
+    /// declaration written as it is (e.g. `html PUBLIC "..." "..."`)
+    pub declaration: Option<Cow<'a, str>>,
 }
 
 impl<'a> DocumentType<'a> {
-    pub fn new(r#type: &'static str, name: &'a str) -> Self {
-        let dtd = DTD::new(r#type, name);
-        let public_id = dtd.public_id();
-        let system_id = dtd.system_id();
+    /// Returns an error for an unknown name, or a name which is not available
+    /// in the spec (e.g. `1.1` in html).
+    pub fn new(spec: Spec, name: &'a str) -> Result<Self, Error> {
+        let dtd = DTD::new(spec, name.parse()?)?;
+
+        Ok(Self {
+            spec,
+            variant: Some(dtd.variant),
+            name,
+            public_id: dtd.public_id(),
+            system_id: dtd.system_id(),
+            encoding: None,
+            declaration: None,
+        })
+    }
+
+    /// Creates an XML prolog like `<?xml version="1.0" encoding="utf-8" ?>`.
+    pub fn xml(encoding: Option<&'a str>) -> Self {
+        Self {
+            spec: Spec::Xml,
+            variant: None,
+            name: "xml",
+            public_id: "",
+            system_id: "",
+            encoding,
+            declaration: None,
+        }
+    }
 
+    /// Creates a doctype which is rendered as `<!DOCTYPE {declaration}>`.
+    ///
+    /// It's treated as xhtml if the declaration mentions `XHTML`.
+    pub fn raw<T>(name: &'a str, declaration: T) -> Self
+    where
+        T: Into<Cow<'a, str>>,
+    {
+        let declaration = declaration.into();
+        let spec = if declaration.contains("XHTML") {
+            Spec::Xhtml
+        } else {
+            Spec::Html
+        };
         Self {
-            dtd,
+            spec,
+            variant: None,
             name,
-            public_id,
-            system_id,
+            public_id: "",
+            system_id: "",
+            encoding: None,
+            declaration: Some(declaration),
+        }
+    }
+
+    /// Returns the output format which the doctype implies.
+    pub fn format(&self) -> Format {
+        match self.spec {
+            Spec::Html => Format::Html,
+            Spec::Xhtml => Format::Xhtml,
+            Spec::Xml => Format::Xml,
         }
     }
 
-    pub fn as_tag(&self) -> String {
-        let mut out = "<!DOCTYPE".to_string();
-        let dec = match self.dtd.spec {
-            "html" => match self.dtd.name {
-                "5" | "html" => " HTML>".to_string(),
-                _ => format!(
-                    " HTML PUBLIC \"{}\" \"{}\">",
-                    self.public_id, self.system_id
-                ),
-            },
-            "xhtml" => format!(
-                " html PUBLIC \"{}\" \"{}\">",
+    pub fn as_tag(&self) -> Markup<'static> {
+        Markup::trusted(self.to_string())
+    }
+}
+
+impl fmt::Display for DocumentType<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(declaration) = &self.declaration {
+            return write!(f, "<!DOCTYPE {}>", declaration);
+        }
+        match (self.spec, self.variant) {
+            (Spec::Xml, _) => write!(
+                f,
+                "<?xml version=\"1.0\" encoding=\"{}\" ?>",
+                self.encoding.unwrap_or("utf-8")
+            ),
+            (Spec::Html, Some(Variant::Html5)) => {
+                f.write_str("<!DOCTYPE HTML>")
+            }
+            (Spec::Html, _) => write!(
+                f,
+                "<!DOCTYPE HTML PUBLIC \"{}\" \"{}\">",
                 self.public_id, self.system_id
             ),
-            _ => "".to_string(),
-        };
-        out.push_str(&dec);
-        out
+            (Spec::Xhtml, Some(Variant::Html5)) => {
+                f.write_str("<!DOCTYPE html>")
+            }
+            (Spec::Xhtml, _) => write!(
+                f,
+                "<!DOCTYPE html PUBLIC \"{}\" \"{}\">",
+                self.public_id, self.system_id
+            ),
+        }
     }
 }
 
@@ -50,35 +126,26 @@ mod test {
 
     #[test]
     fn test_document_type_new() {
-        let doctype = DocumentType::new("unknown", "invalid");
-        assert_eq!(doctype.name, "invalid");
-        assert_eq!(doctype.public_id, "");
-        assert_eq!(doctype.system_id, "");
-
-        let doctype = DocumentType::new("unknown", "strict");
-        assert_eq!(doctype.name, "strict");
-        assert_eq!(doctype.public_id, "");
-        assert_eq!(doctype.system_id, "");
-
-        let doctype = DocumentType::new("xhtml", "invalid");
-        assert_eq!(doctype.name, "invalid");
-        assert_eq!(doctype.public_id, "");
-        assert_eq!(doctype.system_id, "");
-
-        let doctype = DocumentType::new("xhtml", "frameset");
+        let err = DocumentType::new(Spec::Html, "invalid").unwrap_err();
+        assert_eq!("unknown doctype `invalid`", err.to_string());
+
+        let err = DocumentType::new(Spec::Html, "1.1").unwrap_err();
+        assert_eq!(
+            "doctype `1.1` is not available in html mode",
+            err.to_string()
+        );
+        assert!(DocumentType::new(Spec::Xml, "strict").is_err());
+
+        let doctype = DocumentType::new(Spec::Xhtml, "frameset").unwrap();
         assert_eq!(doctype.name, "frameset");
+        assert_eq!(doctype.variant, Some(Variant::Frameset));
         assert_eq!(doctype.public_id, "-//W3C//DTD XHTML 1.0 Frameset//EN");
         assert_eq!(
             doctype.system_id,
             "http://www.w3.org/TR/xhtml1/DTD/xhtml1-frameset.dtd"
         );
 
-        let doctype = DocumentType::new("html", "invalid");
-        assert_eq!(doctype.name, "invalid");
-        assert_eq!(doctype.public_id, "");
-        assert_eq!(doctype.system_id, "");
-
-        let doctype = DocumentType::new("html", "transitional");
+        let doctype = DocumentType::new(Spec::Html, "transitional").unwrap();
         assert_eq!(doctype.name, "transitional");
         assert_eq!(doctype.public_id, "-//W3C//DTD HTML 4.01 Transitional//EN");
         assert_eq!(doctype.system_id, "http://www.w3.org/TR/html4/loose.dtd");
@@ -86,10 +153,10 @@ mod test {
 
     #[test]
     fn test_as_tag() {
-        let doctype = DocumentType::new("html", "5");
+        let doctype = DocumentType::new(Spec::Html, "5").unwrap();
         assert_eq!(doctype.as_tag(), "<!DOCTYPE HTML>");
 
-        let doctype = DocumentType::new("html", "strict");
+        let doctype = DocumentType::new(Spec::Html, "strict").unwrap();
         assert_eq!(
             doctype.as_tag(),
             inline!(
@@ -102,7 +169,7 @@ mod test {
             )
         );
 
-        let doctype = DocumentType::new("html", "frameset");
+        let doctype = DocumentType::new(Spec::Html, "frameset").unwrap();
         assert_eq!(
             doctype.as_tag(),
             inline!(
@@ -115,7 +182,7 @@ mod test {
             )
         );
 
-        let doctype = DocumentType::new("html", "transitional");
+        let doctype = DocumentType::new(Spec::Html, "transitional").unwrap();
         assert_eq!(
             doctype.as_tag(),
             inline!(
@@ -128,4 +195,73 @@ mod test {
             )
         );
     }
+
+    #[test]
+    fn test_as_tag_xhtml() {
+        let doctype = DocumentType::new(Spec::Xhtml, "html").unwrap();
+        assert_eq!(doctype.as_tag(), "<!DOCTYPE html>");
+
+        let doctype = DocumentType::new(Spec::Xhtml, "strict").unwrap();
+        assert_eq!(
+            doctype.as_tag(),
+            inline!(
+                r#"<!DOCTYPE
+ html
+ PUBLIC
+ "-//W3C//DTD XHTML 1.0 Strict//EN"
+ "http://www.w3.org/TR/xhtml1/DTD/xhtml1-strict.dtd"
+>"#
+            )
+        );
+    }
+
+    #[test]
+    fn test_as_tag_raw() {
+        let doctype =
+            DocumentType::raw("html", r#"html SYSTEM "about:legacy-compat""#);
+        assert_eq!("html", doctype.name);
+        assert_eq!("", doctype.public_id);
+        assert_eq!(
+            doctype.as_tag(),
+            r#"<!DOCTYPE html SYSTEM "about:legacy-compat">"#
+        );
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(
+            Format::Html,
+            DocumentType::new(Spec::Html, "strict").unwrap().format()
+        );
+        assert_eq!(
+            Format::Xhtml,
+            DocumentType::new(Spec::Xhtml, "5").unwrap().format()
+        );
+        assert_eq!(Format::Xml, DocumentType::xml(None).format());
+
+        let doctype = DocumentType::raw(
+            "html",
+            r#"html PUBLIC "-//W3C//DTD XHTML+RDFa 1.0//EN" "x.dtd""#,
+        );
+        assert_eq!(Format::Xhtml, doctype.format());
+        let doctype = DocumentType::raw("html", "html SYSTEM \"x.dtd\"");
+        assert_eq!(Format::Html, doctype.format());
+    }
+
+    #[test]
+    fn test_as_tag_xml() {
+        let doctype = DocumentType::xml(None);
+        assert_eq!(doctype.name, "xml");
+        assert_eq!(
+            doctype.as_tag(),
+            r#"<?xml version="1.0" encoding="utf-8" ?>"#
+        );
+
+        let doctype = DocumentType::xml(Some("ISO-8859-1"));
+        assert_eq!(doctype.encoding, Some("ISO-8859-1"));
+        assert_eq!(
+            doctype.as_tag(),
+            r#"<?xml version="1.0" encoding="ISO-8859-1" ?>"#
+        );
+    }
 }
@@ -1,106 +1,176 @@
-use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
 
-type DECLARATIONS = HashMap<&'static str, HashMap<&'static str, &'static str>>;
+use anyhow::{anyhow, Error};
 
-// https://www.w3.org/QA/2002/04/valid-dtd-list.html
-lazy_static! {
-    static ref XHTML_PUBLIC_IDS: [(&'static str, &'static str); 8] = [
-        ("html", ""),
-        ("5", ""),
-        ("1.1", "-//W3C//DTD XHTML 1.1//EN"),
-        ("strict", "-//W3C//DTD XHTML 1.0 Strict//EN"),
-        ("frameset", "-//W3C//DTD XHTML 1.0 Frameset//EN"),
-        ("mobile", "-//WAPFORUM//DTD XHTML Mobile 1.2//EN"),
-        ("basic", "-//W3C//DTD XHTML Basic 1.1//EN"),
-        ("transitional", "-//W3C//DTD XHTML 1.0 Transitional//EN"),
-    ];
-    static ref XHTML_SYSTEM_IDS: [(&'static str, &'static str); 8] = [
-        ("html", ""),
-        ("5", ""),
-        ("1.1", "http://www.w3.org/TR/xhtml11/DTD/xhtml11.dtd"),
-        (
-            "strict",
-            "http://www.w3.org/TR/xhtml1/DTD/xhtml1-strict.dtd"
-        ),
-        (
-            "frameset",
-            "http://www.w3.org/TR/xhtml1/DTD/xhtml1-frameset.dtd"
-        ),
-        (
-            "mobile",
-            "http://www.openmobilealliance.org/tech/DTD/xhtml-mobile12.dtd"
-        ),
-        (
-            "basic",
-            "http://www.w3.org/TR/xhtml-basic/xhtml-basic11.dtd"
-        ),
-        (
-            "transitional",
-            "http://www.w3.org/TR/xhtml1/DTD/xhtml1-transitional.dtd"
-        ),
-    ];
-    static ref HTML_PUBLIC_IDS: [(&'static str, &'static str); 5] = [
-        ("html", ""),
-        ("5", ""),
-        ("strict", "-//W3C//DTD HTML 4.01//EN"),
-        ("frameset", "-//W3C//DTD HTML 4.01 Frameset//EN"),
-        ("transitional", "-//W3C//DTD HTML 4.01 Transitional//EN"),
-    ];
-    static ref HTML_SYSTEM_IDS: [(&'static str, &'static str); 5] = [
-        ("html", ""),
-        ("5", ""),
-        ("strict", "http://www.w3.org/TR/html4/strict.dtd"),
-        ("frameset", "http://www.w3.org/TR/html4/frameset.dtd"),
-        ("transitional", "http://www.w3.org/TR/html4/loose.dtd"),
-    ];
-    pub static ref DOC_TYPES: HashMap<&'static str, DECLARATIONS> =
-        HashMap::from([
-            (
-                "xhtml",
-                HashMap::from([
-                    ("public_id", XHTML_PUBLIC_IDS.iter().cloned().collect()),
-                    ("system_id", XHTML_SYSTEM_IDS.iter().cloned().collect()),
-                ]),
-            ),
-            (
-                "html",
-                HashMap::from([
-                    ("public_id", HTML_PUBLIC_IDS.iter().cloned().collect()),
-                    ("system_id", HTML_SYSTEM_IDS.iter().cloned().collect()),
-                ]),
-            ),
-        ]);
+/// Specification of a doctype.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Spec {
+    Html,
+    Xhtml,
+    Xml,
+}
+
+impl FromStr for Spec {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "html" => Ok(Spec::Html),
+            "xhtml" => Ok(Spec::Xhtml),
+            "xml" => Ok(Spec::Xml),
+            _ => Err(anyhow!("unknown spec `{}`", s)),
+        }
+    }
+}
+
+impl fmt::Display for Spec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Spec::Html => "html",
+            Spec::Xhtml => "xhtml",
+            Spec::Xml => "xml",
+        };
+        f.write_str(s)
+    }
+}
+
+/// Named doctype like `strict` in `doctype strict`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Variant {
+    /// `html` or `5`
+    Html5,
+    Strict,
+    Frameset,
+    Transitional,
+    Mobile,
+    Basic,
+    /// `1.1`
+    Xhtml11,
+}
+
+impl FromStr for Variant {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "html" | "5" => Ok(Variant::Html5),
+            "strict" => Ok(Variant::Strict),
+            "frameset" => Ok(Variant::Frameset),
+            "transitional" => Ok(Variant::Transitional),
+            "mobile" => Ok(Variant::Mobile),
+            "basic" => Ok(Variant::Basic),
+            "1.1" => Ok(Variant::Xhtml11),
+            _ => Err(anyhow!("unknown doctype `{}`", s)),
+        }
+    }
+}
+
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Variant::Html5 => "html",
+            Variant::Strict => "strict",
+            Variant::Frameset => "frameset",
+            Variant::Transitional => "transitional",
+            Variant::Mobile => "mobile",
+            Variant::Basic => "basic",
+            Variant::Xhtml11 => "1.1",
+        };
+        f.write_str(s)
+    }
 }
 
-#[derive(Clone, Debug, Eq, PartialEq)]
-pub struct DTD<'a> {
-    pub spec: &'a str,
-    pub name: &'a str,
+/// Document type definition, which is a known pair of spec and variant.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct DTD {
+    pub spec: Spec,
+    pub variant: Variant,
 }
 
-impl<'a> DTD<'a> {
-    // TODO: check arguments
-    pub fn new(spec: &'a str, name: &'a str) -> Self {
-        DTD { spec, name }
+// https://www.w3.org/QA/2002/04/valid-dtd-list.html
+pub const DOC_TYPES: [DTD; 11] = [
+    DTD::of(Spec::Html, Variant::Html5),
+    DTD::of(Spec::Html, Variant::Strict),
+    DTD::of(Spec::Html, Variant::Frameset),
+    DTD::of(Spec::Html, Variant::Transitional),
+    DTD::of(Spec::Xhtml, Variant::Html5),
+    DTD::of(Spec::Xhtml, Variant::Xhtml11),
+    DTD::of(Spec::Xhtml, Variant::Strict),
+    DTD::of(Spec::Xhtml, Variant::Frameset),
+    DTD::of(Spec::Xhtml, Variant::Mobile),
+    DTD::of(Spec::Xhtml, Variant::Basic),
+    DTD::of(Spec::Xhtml, Variant::Transitional),
+];
+
+impl DTD {
+    const fn of(spec: Spec, variant: Variant) -> Self {
+        DTD { spec, variant }
     }
 
-    fn decralation(&self, id: &'a str) -> Option<&'static str> {
-        let value = DOC_TYPES
-            .get(self.spec)?
-            .get(id)?
-            .get(self.name)
-            .unwrap_or(&"");
-        Some(value)
+    /// Returns an error if the pair is not in `DOC_TYPES`.
+    pub fn new(spec: Spec, variant: Variant) -> Result<Self, Error> {
+        let dtd = DTD::of(spec, variant);
+        if !DOC_TYPES.contains(&dtd) {
+            return Err(anyhow!(
+                "doctype `{}` is not available in {} mode",
+                variant,
+                spec
+            ));
+        }
+        Ok(dtd)
+    }
+
+    /// Returns (public_id, system_id).
+    fn ids(&self) -> (&'static str, &'static str) {
+        match (self.spec, self.variant) {
+            (Spec::Html, Variant::Strict) => (
+                "-//W3C//DTD HTML 4.01//EN",
+                "http://www.w3.org/TR/html4/strict.dtd",
+            ),
+            (Spec::Html, Variant::Frameset) => (
+                "-//W3C//DTD HTML 4.01 Frameset//EN",
+                "http://www.w3.org/TR/html4/frameset.dtd",
+            ),
+            (Spec::Html, Variant::Transitional) => (
+                "-//W3C//DTD HTML 4.01 Transitional//EN",
+                "http://www.w3.org/TR/html4/loose.dtd",
+            ),
+            (Spec::Xhtml, Variant::Xhtml11) => (
+                "-//W3C//DTD XHTML 1.1//EN",
+                "http://www.w3.org/TR/xhtml11/DTD/xhtml11.dtd",
+            ),
+            (Spec::Xhtml, Variant::Strict) => (
+                "-//W3C//DTD XHTML 1.0 Strict//EN",
+                "http://www.w3.org/TR/xhtml1/DTD/xhtml1-strict.dtd",
+            ),
+            (Spec::Xhtml, Variant::Frameset) => (
+                "-//W3C//DTD XHTML 1.0 Frameset//EN",
+                "http://www.w3.org/TR/xhtml1/DTD/xhtml1-frameset.dtd",
+            ),
+            (Spec::Xhtml, Variant::Mobile) => (
+                "-//WAPFORUM//DTD XHTML Mobile 1.2//EN",
+                "http://www.openmobilealliance.org/tech/DTD/xhtml-mobile12.dtd",
+            ),
+            (Spec::Xhtml, Variant::Basic) => (
+                "-//W3C//DTD XHTML Basic 1.1//EN",
+                "http://www.w3.org/TR/xhtml-basic/xhtml-basic11.dtd",
+            ),
+            (Spec::Xhtml, Variant::Transitional) => (
+                "-//W3C//DTD XHTML 1.0 Transitional//EN",
+                "http://www.w3.org/TR/xhtml1/DTD/xhtml1-transitional.dtd",
+            ),
+            // html5 has no identifiers
+            _ => ("", ""),
+        }
     }
 
-    // returns empty &'static str if invalid spec or name is given.
     pub fn public_id(&self) -> &'static str {
-        self.decralation("public_id").unwrap_or("")
+        self.ids().0
     }
 
-    // returns empty &'static str if invalid spec or name is given.
     pub fn system_id(&self) -> &'static str {
-        self.decralation("system_id").unwrap_or("")
+        self.ids().1
     }
 }
 
@@ -109,82 +179,93 @@ mod test {
     use super::*;
 
     #[test]
-    fn test_new() {
-        let dtd = DTD::new("unknown", "");
-        assert_eq!(dtd.spec, "unknown");
-        assert_eq!(dtd.name, "");
+    fn test_spec_from_str() {
+        assert_eq!(Spec::Html, "html".parse().unwrap());
+        assert_eq!(Spec::Xhtml, "xhtml".parse().unwrap());
+        assert_eq!(Spec::Xml, "xml".parse().unwrap());
+        assert!("unknown".parse::<Spec>().is_err());
 
-        let dtd = DTD::new("xhtml", "invalid");
-        assert_eq!(dtd.spec, "xhtml");
-        assert_eq!(dtd.name, "invalid");
+        for s in &[Spec::Html, Spec::Xhtml, Spec::Xml] {
+            assert_eq!(*s, s.to_string().parse().unwrap());
+        }
+    }
 
-        let dtd = DTD::new("xhtml", "frameset");
-        assert_eq!(dtd.spec, "xhtml");
-        assert_eq!(dtd.name, "frameset");
+    #[test]
+    fn test_variant_from_str() {
+        assert_eq!(Variant::Html5, "html".parse().unwrap());
+        assert_eq!(Variant::Html5, "5".parse().unwrap());
+        assert_eq!(Variant::Xhtml11, "1.1".parse().unwrap());
+        assert_eq!(Variant::Strict, "strict".parse().unwrap());
 
-        let dtd = DTD::new("html", "invalid");
-        assert_eq!(dtd.spec, "html");
-        assert_eq!(dtd.name, "invalid");
+        let err = "unknown".parse::<Variant>().unwrap_err();
+        assert_eq!("unknown doctype `unknown`", err.to_string());
 
-        let dtd = DTD::new("html", "strict");
-        assert_eq!(dtd.spec, "html");
-        assert_eq!(dtd.name, "strict");
+        for d in DOC_TYPES.iter() {
+            assert_eq!(d.variant, d.variant.to_string().parse().unwrap());
+        }
     }
 
     #[test]
-    fn test_public_id() {
-        let dtd = DTD::new("invalid", "");
-        assert_eq!(dtd.public_id(), "");
+    fn test_new() {
+        let dtd = DTD::new(Spec::Xhtml, Variant::Frameset).unwrap();
+        assert_eq!(dtd.spec, Spec::Xhtml);
+        assert_eq!(dtd.variant, Variant::Frameset);
 
-        let dtd = DTD::new("invalid", "html");
-        assert_eq!(dtd.public_id(), "");
+        let dtd = DTD::new(Spec::Html, Variant::Strict).unwrap();
+        assert_eq!(dtd.spec, Spec::Html);
+        assert_eq!(dtd.variant, Variant::Strict);
 
-        let dtd = DTD::new("xhtml", "unknown");
-        assert_eq!(dtd.public_id(), "");
+        let err = DTD::new(Spec::Html, Variant::Mobile).unwrap_err();
+        assert_eq!(
+            "doctype `mobile` is not available in html mode",
+            err.to_string()
+        );
+        assert!(DTD::new(Spec::Xml, Variant::Html5).is_err());
+    }
 
-        let dtd = DTD::new("xhtml", "5");
+    #[test]
+    fn test_public_id() {
+        let dtd = DTD::new(Spec::Xhtml, Variant::Html5).unwrap();
         assert_eq!(dtd.public_id(), "");
 
-        let dtd = DTD::new("xhtml", "1.1");
+        let dtd = DTD::new(Spec::Xhtml, Variant::Xhtml11).unwrap();
         assert_eq!(dtd.public_id(), "-//W3C//DTD XHTML 1.1//EN");
 
-        let dtd = DTD::new("html", "unknown");
+        let dtd = DTD::new(Spec::Html, Variant::Html5).unwrap();
         assert_eq!(dtd.public_id(), "");
 
-        let dtd = DTD::new("html", "5");
-        assert_eq!(dtd.public_id(), "");
-
-        let dtd = DTD::new("html", "strict");
+        let dtd = DTD::new(Spec::Html, Variant::Strict).unwrap();
         assert_eq!(dtd.public_id(), "-//W3C//DTD HTML 4.01//EN");
     }
 
     #[test]
     fn test_system_id() {
-        let dtd = DTD::new("invalid", "");
-        assert_eq!(dtd.system_id(), "");
-
-        let dtd = DTD::new("invalid", "html");
-        assert_eq!(dtd.system_id(), "");
-
-        let dtd = DTD::new("xhtml", "unknown");
-        assert_eq!(dtd.system_id(), "");
-
-        let dtd = DTD::new("xhtml", "5");
+        let dtd = DTD::new(Spec::Xhtml, Variant::Html5).unwrap();
         assert_eq!(dtd.system_id(), "");
 
-        let dtd = DTD::new("xhtml", "1.1");
+        let dtd = DTD::new(Spec::Xhtml, Variant::Xhtml11).unwrap();
         assert_eq!(
             dtd.system_id(),
             "http://www.w3.org/TR/xhtml11/DTD/xhtml11.dtd"
         );
 
-        let dtd = DTD::new("html", "unknown");
-        assert_eq!(dtd.system_id(), "");
-
-        let dtd = DTD::new("html", "5");
+        let dtd = DTD::new(Spec::Html, Variant::Html5).unwrap();
         assert_eq!(dtd.system_id(), "");
 
-        let dtd = DTD::new("html", "strict");
+        let dtd = DTD::new(Spec::Html, Variant::Strict).unwrap();
         assert_eq!(dtd.system_id(), "http://www.w3.org/TR/html4/strict.dtd");
     }
+
+    #[test]
+    fn test_doc_types() {
+        let html = DOC_TYPES.iter().filter(|d| d.spec == Spec::Html).count();
+        assert_eq!(4, html);
+        assert!(DOC_TYPES.iter().all(|d| d.spec != Spec::Xml));
+        assert!(
+            DOC_TYPES
+                .iter()
+                .all(|d| (d.variant == Variant::Html5)
+                    == d.public_id().is_empty())
+        );
+    }
 }
@@ -1,8 +1,6 @@
 #![allow(clippy::upper_case_acronyms)]
 
 extern crate anyhow;
-#[macro_use]
-extern crate lazy_static;
 extern crate pest;
 #[macro_use]
 extern crate pest_derive;
@@ -17,9 +15,9 @@ macro_rules! inline {
     };
 }
 
-mod dtd;
 pub mod document;
 pub mod document_type;
+pub mod dtd;
 
 pub mod parser;
 pub mod renderer;
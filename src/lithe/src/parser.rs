@@ -1,21 +1,279 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
 use anyhow::Error;
-use pest::Parser;
-use pest::iterators::Pairs;
+use pest::{Parser, Position, Span};
+use pest::error::{Error as ParseError, ErrorVariant, LineColLocation};
+use pest::iterators::{Pair, Pairs};
 
-use crate::document::{Attr, Document, Element, NamedNodeMap};
+use crate::document::{
+    Attr, Comment, CommentKind, ConditionalComment, Document, Element,
+    ElementKind, NamedNodeMap, Node, RawHtml, Text,
+};
 use crate::document_type::DocumentType;
+use crate::dtd::{Spec, Variant, DTD};
+use crate::renderer::Format;
 
 #[derive(Parser)]
 #[grammar = "grammar.pest"]
 pub struct LitheParser;
 
-pub fn parse(s: &str) -> Result<Document, Error> {
-    let mut result = LitheParser::parse(Rule::document, s)?;
+/// Options for parsing.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ParseOptions {
+    /// Mode (html or xhtml) used to look up the doctype. If `None`, it's
+    /// inferred from the doctype (`1.1`, `basic` and `mobile` are xhtml only,
+    /// and the others fall back to html).
+    pub format: Option<Format>,
+    /// Extra named doctypes (name to declaration), which are looked up
+    /// before the built-in ones.
+    pub doctypes: HashMap<String, String>,
+    /// Width of a tab in indentation. If set, tabs are expanded into spaces
+    /// (see `expand_tabs`) so that tabs and spaces can be mixed.
+    pub tab_width: Option<usize>,
+}
+
+impl ParseOptions {
+    /// Registers a named doctype which can be used as `doctype {name}`.
+    ///
+    /// ```rust
+    /// use lithe::parser::{parse_with_options, ParseOptions};
+    /// use lithe::renderer::render;
+    ///
+    /// let mut options = ParseOptions::default();
+    /// options.register_doctype("legacy", r#"html SYSTEM "about:legacy-compat""#);
+    ///
+    /// let doc = parse_with_options("doctype legacy", &options).unwrap();
+    /// assert_eq!(
+    ///     r#"<!DOCTYPE html SYSTEM "about:legacy-compat">"#,
+    ///     render(&doc).unwrap()
+    /// );
+    /// ```
+    pub fn register_doctype<N, D>(
+        &mut self,
+        name: N,
+        declaration: D,
+    ) -> &mut Self
+    where
+        N: Into<String>,
+        D: Into<String>,
+    {
+        self.doctypes.insert(name.into(), declaration.into());
+        self
+    }
+}
+
+pub fn parse(s: &str) -> Result<Document<'_>, Error> {
+    parse_with_options(s, &ParseOptions::default())
+}
+
+pub fn parse_with_options<'a>(
+    s: &'a str,
+    options: &ParseOptions,
+) -> Result<Document<'a>, Error> {
+    parse_source(s, options, false)
+}
+
+/// Parses a partial template, which must not have any doctype.
+///
+/// ```rust
+/// use lithe::parser::parse_fragment;
+/// use lithe::renderer::render;
+///
+/// let doc = parse_fragment("ul\n  li: a href=\"/\" Home\n").unwrap();
+/// assert!(doc.r#type.is_none());
+/// assert_eq!(
+///     "<ul><li><a href=\"/\">Home</a></li></ul>",
+///     render(&doc).unwrap()
+/// );
+/// ```
+pub fn parse_fragment(s: &str) -> Result<Document<'_>, Error> {
+    parse_fragment_with_options(s, &ParseOptions::default())
+}
+
+pub fn parse_fragment_with_options<'a>(
+    s: &'a str,
+    options: &ParseOptions,
+) -> Result<Document<'a>, Error> {
+    parse_source(s, options, true)
+}
+
+fn parse_source<'a>(
+    s: &'a str,
+    options: &ParseOptions,
+    fragment: bool,
+) -> Result<Document<'a>, Error> {
+    match options.tab_width.map(|w| expand_tabs(s, w)) {
+        Some(Cow::Owned(expanded)) => {
+            let src = Source::expanded(s, &expanded);
+            parse_document(&expanded, &src, options, fragment)
+        }
+        _ => parse_document(s, &Source::new(s), options, fragment),
+    }
+}
+
+fn parse_document<'a>(
+    input: &str,
+    src: &Source<'a>,
+    options: &ParseOptions,
+    fragment: bool,
+) -> Result<Document<'a>, Error> {
+    let mut result = LitheParser::parse(Rule::document, input)
+        .map_err(|e| indentation_error(input, e))?;
 
-    let doc = build(&mut result);
+    if let Some(pair) = result
+        .peek()
+        .filter(|p| fragment && p.as_rule() == Rule::doctype)
+    {
+        let message = "fragment must not have a doctype".to_string();
+        return Err(ParseError::<Rule>::new_from_span(
+            ErrorVariant::CustomError { message },
+            pair.as_span(),
+        )
+        .into());
+    }
+    let doc = build(&mut result, src, options)?;
     Ok(doc)
 }
 
+/// Expands tabs in indentation into `tab_width` spaces.
+///
+/// Tabs can be used for indentation as they are, but a template mixing tabs
+/// and spaces needs to be expanded before parsing (or parsed with
+/// `ParseOptions::tab_width`).
+///
+/// ```rust
+/// use lithe::parser::{expand_tabs, parse};
+///
+/// let s = expand_tabs("doctype html\nul\n\tli\n    li\n", 4);
+/// let doc = parse(&s).unwrap();
+/// assert_eq!(2, doc.children[0].as_element().unwrap().children.len());
+/// ```
+pub fn expand_tabs(s: &str, tab_width: usize) -> Cow<'_, str> {
+    if !s.contains('\t') {
+        return Cow::Borrowed(s);
+    }
+    let tab = " ".repeat(tab_width);
+    let mut out = String::with_capacity(s.len());
+    for line in s.split_inclusive('\n') {
+        let content = line.trim_start_matches([' ', '\t']);
+        let indent = &line[..line.len() - content.len()];
+        out.push_str(&indent.replace('\t', &tab));
+        out.push_str(content);
+    }
+    Cow::Owned(out)
+}
+
+/// Template which the document borrows strings from.
+///
+/// If tabs are expanded, the parsed input is the expanded copy, and offsets
+/// in it are mapped back onto the original template. Only indentation
+/// differs between them, so an offset is moved by the difference of the
+/// indentation of its line.
+struct Source<'a> {
+    text: &'a str,
+    /// (line start, content start) in the parsed input and content start in
+    /// the original template for each line (empty if not expanded)
+    lines: Vec<(usize, usize, usize)>,
+}
+
+impl<'a> Source<'a> {
+    fn new(text: &'a str) -> Self {
+        Self {
+            text,
+            lines: vec![],
+        }
+    }
+
+    fn expanded(text: &'a str, expanded: &str) -> Self {
+        let mut lines = vec![];
+        let (mut e, mut o) = (0, 0);
+        for (el, ol) in expanded.split('\n').zip(text.split('\n')) {
+            let (ei, oi) = (indentation(el).len(), indentation(ol).len());
+            lines.push((e, e + ei, o + oi));
+            e += el.len() + 1;
+            o += ol.len() + 1;
+        }
+        Self { text, lines }
+    }
+
+    /// Returns the offset in the original template. An offset in
+    /// indentation is moved to the start of the content.
+    fn offset(&self, pos: usize) -> usize {
+        if self.lines.is_empty() {
+            return pos;
+        }
+        let i = self.lines.partition_point(|l| l.0 <= pos).max(1) - 1;
+        let (_, content, original) = self.lines[i];
+        original + pos.saturating_sub(content)
+    }
+
+    fn slice(&self, start: usize, end: usize) -> &'a str {
+        &self.text[self.offset(start)..self.offset(end)]
+    }
+
+    fn str(&self, span: Span) -> &'a str {
+        self.slice(span.start(), span.end())
+    }
+}
+
+fn indentation(line: &str) -> &str {
+    &line[..line.len() - line.trim_start_matches([' ', '\t']).len()]
+}
+
+/// Replaces an error at the beginning of an indented line with an indentation
+/// error, if the line is dedented to a level which doesn't match any outer
+/// one.
+fn indentation_error(s: &str, err: ParseError<Rule>) -> ParseError<Rule> {
+    let (line_no, col) = match err.line_col {
+        LineColLocation::Pos(p) => p,
+        _ => return err,
+    };
+    let lines = s.split('\n').collect::<Vec<_>>();
+    let line = match lines.get(line_no - 1) {
+        Some(l) => l,
+        None => return err,
+    };
+    let indent = indentation(line);
+    if indent.is_empty() || col != indent.chars().count() + 1 {
+        return err;
+    }
+
+    // indentations of the previous line and its ancestors
+    let mut levels: Vec<&str> = vec![];
+    for l in lines[..line_no - 1].iter().rev() {
+        if l.trim().is_empty() {
+            continue;
+        }
+        let i = indentation(l);
+        if levels.last().is_none_or(|last| i.len() < last.len()) {
+            levels.push(i);
+        }
+    }
+    let consistent = match levels.first() {
+        Some(p) => {
+            (indent.len() > p.len() && indent.starts_with(p))
+                || levels.contains(&indent)
+        }
+        None => false,
+    };
+    if consistent {
+        return err;
+    }
+
+    let offset: usize = lines[..line_no - 1].iter().map(|l| l.len() + 1).sum();
+    let pos = match Position::new(s, offset + indent.len()) {
+        Some(p) => p,
+        None => return err,
+    };
+    let message = format!(
+        "inconsistent indentation at line {} (it doesn't match any outer \
+         level)",
+        line_no
+    );
+    ParseError::new_from_pos(ErrorVariant::CustomError { message }, pos)
+}
+
 /// Builds structured tree data.
 ///
 /// At the moment, this should look like:
@@ -26,6 +284,7 @@ pub fn parse(s: &str) -> Result<Document, Error> {
 ///   head
 ///     link rel="stylesheet" href="style.css"
 ///   body
+///     / TODO
 /// "#;
 /// ```
 ///
@@ -33,163 +292,490 @@ pub fn parse(s: &str) -> Result<Document, Error> {
 /// [src/lithe/src/parser.rs:16] &doc = Document {
 ///    type: Some(
 ///        DocumentType {
-///            dtd: DTD {
-///                spec: "html",
-///                name: "html",
-///            },
+///            spec: Html,
+///            variant: Some(
+///                Html5,
+///            ),
 ///            name: "html",
 ///            public_id: "",
 ///            system_id: "",
+///            encoding: None,
+///            declaration: None,
 ///        },
 ///    ),
 ///    children: [
-///        Element {
-///            name: "html",
-///            attributes: [],
-///            children: [
-///                Element {
-///                    name: "head",
-///                    attributes: [],
-///                    children: [
+///        Element(
+///            Element {
+///                name: "html",
+///                attributes: [],
+///                children: [
+///                    Element(
 ///                        Element {
-///                            name: "link",
-///                            attributes: [
-///                                Attr {
-///                                    name: "rel",
-///                                    value: "stylesheet",
-///                                },
-///                                Attr {
-///                                    name: "href",
-///                                    value: "style.css",
-///                                },
+///                            name: "head",
+///                            attributes: [],
+///                            children: [
+///                                Element(
+///                                    Element {
+///                                        name: "link",
+///                                        attributes: [
+///                                            Attr {
+///                                                name: "rel",
+///                                                value: Some(
+///                                                    "stylesheet",
+///                                                ),
+///                                            },
+///                                            Attr {
+///                                                name: "href",
+///                                                value: Some(
+///                                                    "style.css",
+///                                                ),
+///                                            },
+///                                        ],
+///                                        children: [],
+///                                        leading_whitespace: false,
+///                                        trailing_whitespace: false,
+///                                        self_closing: false,
+///                                    },
+///                                ),
 ///                            ],
-///                            children: [],
+///                            leading_whitespace: false,
+///                            trailing_whitespace: false,
+///                            self_closing: false,
 ///                        },
-///                    ],
-///                },
-///                Element {
-///                    name: "body",
-///                    attributes: [],
-///                    children: [],
-///                },
-///            ],
-///        },
+///                    ),
+///                    Element(
+///                        Element {
+///                            name: "body",
+///                            attributes: [],
+///                            children: [
+///                                Comment(
+///                                    Comment {
+///                                        kind: Code,
+///                                        data: "TODO",
+///                                    },
+///                                ),
+///                            ],
+///                            leading_whitespace: false,
+///                            trailing_whitespace: false,
+///                            self_closing: false,
+///                        },
+///                    ),
+///                ],
+///                leading_whitespace: false,
+///                trailing_whitespace: false,
+///                self_closing: false,
+///            },
+///        ),
 ///    ],
 ///}
 /// ```
-fn build<'a>(pairs: &mut Pairs<'a, Rule>) -> Document<'a> {
+fn build<'a>(
+    pairs: &mut Pairs<Rule>,
+    src: &Source<'a>,
+    options: &ParseOptions,
+) -> Result<Document<'a>, Error> {
     let mut doc = Document::new();
 
-    #[allow(clippy::useless_conversion)]
-    for pair in pairs.into_iter() {
-        let rule = pair.as_rule();
-        let inner = pair.into_inner();
-        match rule {
-            Rule::EOI => {
-                return doc;
-            }
-            Rule::doctype => {
-                for i in inner {
-                    if i.as_rule() == Rule::doctype_value {
-                        // TODO: mode (html|xhtml)
-                        let (spec, name) = match i.as_span().as_str() {
-                            "html" => ("html", "html"),
-                            "5" => ("html", "5"),
-                            _ => ("", ""),
-                        };
-                        let doctype = DocumentType::new(spec, name);
-                        doc.r#type = Some(doctype);
-                        // TODO: Is there any way? (instead of reusing pairs)
-                        doc.children = build_element(pairs);
-                        break;
+    if let Some(pair) = pairs.peek().filter(|p| p.as_rule() == Rule::doctype) {
+        pairs.next();
+        for i in pair.into_inner() {
+            if i.as_rule() == Rule::doctype_value {
+                let span = i.as_span();
+                let mut inner = i.into_inner();
+                let doctype = match inner.next() {
+                    Some(p) if p.as_rule() == Rule::xml_doctype => {
+                        let encoding = inner
+                            .next()
+                            .filter(|e| e.as_rule() == Rule::encoding)
+                            .map(|e| src.str(e.as_span()));
+                        DocumentType::xml(encoding)
                     }
-                }
-                return doc;
+                    Some(p) if p.as_rule() == Rule::raw_doctype => {
+                        let declaration = src.str(p.as_span()).trim_end();
+                        let name = declaration
+                            .split(' ')
+                            .next()
+                            .unwrap_or(declaration);
+                        DocumentType::raw(name, declaration)
+                    }
+                    _ => build_doctype(span, src, options)?,
+                };
+                doc.r#type = Some(doctype);
             }
-            _ => {}
         }
     }
-    doc
+    doc.children = build_nodes(pairs, src)?;
+    Ok(doc)
+}
+
+/// Looks up a registered doctype, or an html or xhtml doctype by its name in
+/// the given (or inferred) mode.
+fn build_doctype<'a>(
+    span: Span,
+    src: &Source<'a>,
+    options: &ParseOptions,
+) -> Result<DocumentType<'a>, ParseError<Rule>> {
+    let name = src.str(span);
+    if let Some(declaration) = options.doctypes.get(name) {
+        return Ok(DocumentType::raw(name, declaration.clone()));
+    }
+    let to_error = |e: Error| {
+        let message = e.to_string();
+        ParseError::new_from_span(ErrorVariant::CustomError { message }, span)
+    };
+    let variant = name.parse::<Variant>().map_err(to_error)?;
+    let spec = match options.format {
+        Some(Format::Html) => Spec::Html,
+        Some(Format::Xhtml) | Some(Format::Xml) => Spec::Xhtml,
+        // falls back to html unless it's xhtml only (e.g. `1.1`)
+        None if DTD::new(Spec::Html, variant).is_ok() => Spec::Html,
+        None => Spec::Xhtml,
+    };
+    DocumentType::new(spec, name).map_err(to_error)
+}
+
+/// Decodes backslash escapes in a quoted attribute value.
+///
+/// `\\`, `\"`, `\'`, `\n`, `\r` and `\t` are decoded. Any other escape
+/// is kept as it is (e.g. `\d` in `pattern`).
+fn unescape(s: &str) -> Cow<'_, str> {
+    if !s.contains('\\') {
+        return Cow::Borrowed(s);
+    }
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('t') => out.push('\t'),
+            Some(e @ ('\\' | '"' | '\'')) => out.push(e),
+            Some(e) => {
+                out.push(c);
+                out.push(e);
+            }
+            None => out.push(c),
+        }
+    }
+    Cow::Owned(out)
+}
+
+/// Adds an attribute. Classes are merged into one `class` attribute, and
+/// the last `id` wins.
+fn push_attribute<'a>(attributes: &mut NamedNodeMap<'a>, attr: Attr<'a>) {
+    if attr.name == "class" || attr.name == "id" {
+        if let Some(a) = attributes.iter_mut().find(|a| a.name == attr.name) {
+            if attr.name == "class" {
+                let value = [&a.value, &attr.value]
+                    .iter()
+                    .filter_map(|v| v.as_deref())
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                a.value = Some(value.into());
+            } else {
+                a.value = attr.value;
+            }
+            return;
+        }
+    }
+    attributes.push(attr);
 }
 
-fn build_attributes<'a>(pairs: &mut Pairs<'a, Rule>) -> Vec<Attr<'a>> {
+/// Builds the attributes of the element. It stops at a bare name which is
+/// not an attribute of the element (see `build_inline_text`).
+fn build_attributes<'a>(
+    pairs: &mut Pairs<Rule>,
+    src: &Source<'a>,
+    element: &Element,
+) -> Result<Vec<Attr<'a>>, Error> {
     let mut attributes: NamedNodeMap = vec![];
 
-    for pair in pairs {
+    while let Some(pair) = pairs.peek() {
         let rule = pair.as_rule();
+        let span = pair.as_span();
         let mut inner = pair.into_inner();
-        match rule {
-            Rule::link_attribute => {
-                // https://developer.mozilla.org/en-US/docs/Web/HTML/Element/link
-                // NOTE: array_chunks() or next_chunk()?
-                while let Some(i) = inner.next() {
-                    let name = i.as_span().as_str();
-                    let value =
-                        inner.next().map_or("", |a| a.as_span().as_str());
-                    attributes.push(Attr { name, value });
-                }
-            }
-            _ => {
-                // global attributes
-                let mut i = inner.take(2);
-                let name = if let Some(a) = i.next() {
-                    a.as_span().as_str()
+        let attr = match rule {
+            Rule::class_shortcut | Rule::id_shortcut => {
+                let name = if rule == Rule::class_shortcut {
+                    "class"
                 } else {
+                    "id"
+                };
+                let value = inner.next().map_or("", |a| src.str(a.as_span()));
+                Attr::new(name, value)
+            }
+            Rule::attribute | Rule::wrapped_attribute => {
+                let name = inner.next().map_or("", |a| src.str(a.as_span()));
+                // e.g. `required` in `p required fields`
+                if rule == Rule::attribute
+                    && !span.as_str().contains('=')
+                    && element.kind() == ElementKind::Normal
+                    && !element.has_boolean_attribute(name)
+                {
                     break;
+                }
+                match inner.next().and_then(|v| v.into_inner().next()) {
+                    Some(v) => Attr::new(name, unescape(src.str(v.as_span()))),
+                    None => Attr::boolean(name),
+                }
+            }
+            Rule::unclosed_wrapper => {
+                let pos = span.start_pos();
+                let open = span.as_str().chars().next();
+                let close = match open {
+                    Some('(') => ')',
+                    Some('[') => ']',
+                    _ => '}',
                 };
-                let value = i.next().map_or("", |a| a.as_span().as_str());
-                attributes.push(Attr { name, value });
+                let message = format!(
+                    "attribute wrapper is not closed (expected `{}`)",
+                    close
+                );
+                let variant = ErrorVariant::CustomError { message };
+                let err = ParseError::<Rule>::new_from_pos(variant, pos);
+                return Err(err.into());
+            }
+            Rule::leading_whitespace | Rule::trailing_whitespace => {
+                pairs.next();
+                continue;
+            }
+            _ => break,
+        };
+        pairs.next();
+        push_attribute(&mut attributes, attr);
+    }
+    Ok(attributes)
+}
+
+/// Builds a text from the first line and its indented lines of `|`, `'`, `/!`
+/// and `/`.
+///
+/// The left margin is set at the symbol + one space if the text starts on the
+/// same line, otherwise at the indentation of the first line. Any additional
+/// spaces are kept.
+fn build_text_lines(pair: Pair<Rule>) -> String {
+    let start = pair.as_span().start();
+    let mut lines: Vec<String> = vec![];
+    let mut margin = None;
+    let mut blank_lines = 0;
+
+    for i in pair.into_inner() {
+        match i.as_rule() {
+            Rule::text => {
+                margin = Some(i.as_span().start() - start);
+                lines.push(i.as_span().as_str().to_string());
+            }
+            Rule::text_blank_line if margin.is_some() => {
+                blank_lines += 1;
+            }
+            Rule::text_line => {
+                let line = i.as_span().as_str();
+                let content = line.trim_start_matches([' ', '\t']);
+                let indent = line.len() - content.len();
+                // lines can be indented less than the first line
+                let m = margin.map_or(indent, |m: usize| m.min(indent));
+                margin = Some(m);
+
+                lines.extend((0..blank_lines).map(|_| "".to_string()));
+                blank_lines = 0;
+                lines.push(format!("{}{}", " ".repeat(indent - m), content));
+            }
+            _ => {}
+        }
+    }
+    lines.join("\n")
+}
+
+/// Builds the text of a normal element which starts with a bare name that is
+/// not its attribute, up to the end of the line. (e.g. `required fields` in
+/// `p required fields`)
+fn build_inline_text<'a>(
+    pairs: &mut Pairs<Rule>,
+    src: &Source<'a>,
+) -> Option<Text<'a>> {
+    let start = pairs
+        .peek()
+        .filter(|p| p.as_rule() == Rule::attribute)?
+        .as_span()
+        .start();
+    let mut end = start;
+    while let Some(p) = pairs
+        .peek()
+        .filter(|p| matches!(p.as_rule(), Rule::attribute | Rule::text))
+    {
+        end = p.as_span().end();
+        pairs.next();
+    }
+    Some(Text::new(src.slice(start, end)))
+}
+
+fn build_text_block<'a>(pair: Pair<Rule>) -> Text<'a> {
+    let trailing_space = pair
+        .clone()
+        .into_inner()
+        .any(|i| i.as_rule() == Rule::trailing_space_text_symbol);
+
+    let mut data = build_text_lines(pair);
+    if trailing_space {
+        data.push(' ');
+    }
+    Text::new(data)
+}
+
+/// Builds the content of a raw text element (e.g. `script`) from the text on
+/// the same line and the indented lines, which are not parsed as tags.
+///
+/// The whitespace is kept except for the common indentation of the lines.
+fn build_raw_text(pairs: Pairs<Rule>) -> String {
+    let mut lines: Vec<&str> = vec![];
+    let mut body: Vec<Option<&str>> = vec![];
+    let mut margin: Option<usize> = None;
+
+    for i in pairs {
+        match i.as_rule() {
+            Rule::text => lines.push(i.as_str()),
+            Rule::text_blank_line if !lines.is_empty() || !body.is_empty() => {
+                body.push(None);
+            }
+            Rule::text_line => {
+                let line = i.as_str();
+                let indent =
+                    line.len() - line.trim_start_matches([' ', '\t']).len();
+                margin = Some(margin.map_or(indent, |m| m.min(indent)));
+                body.push(Some(line));
             }
+            _ => {}
+        }
+    }
+    let m = margin.unwrap_or(0);
+    lines.extend(body.into_iter().map(|l| l.map_or("", |l| &l[m..])));
+    lines.join("\n")
+}
+
+fn build_element<'a>(
+    pair: Pair<Rule>,
+    src: &Source<'a>,
+) -> Result<Element<'a>, Error> {
+    let raw_text = pair.as_rule() == Rule::raw_text_tag;
+    let mut inner = pair.into_inner();
+    let mut element = Element::new();
+
+    // implicit div
+    element.name = match inner.peek() {
+        Some(n)
+            if [
+                Rule::tag_name,
+                Rule::void_tag_name,
+                Rule::raw_text_tag_name,
+            ]
+            .contains(&n.as_rule()) =>
+        {
+            inner.next();
+            n.as_span().as_str().to_string()
+        }
+        _ => "div".to_string(),
+    };
+    for i in inner.clone() {
+        match i.as_rule() {
+            Rule::leading_whitespace => element.leading_whitespace = true,
+            Rule::trailing_whitespace => element.trailing_whitespace = true,
+            Rule::self_closing => element.self_closing = true,
+            _ => {}
+        }
+    }
+    element.attributes = build_attributes(&mut inner, src, &element)?;
+
+    if raw_text {
+        let data = build_raw_text(inner);
+        if !data.is_empty() {
+            element.children = vec![Node::Text(Text::new(data))];
+        }
+    } else if element.is_self_closing() {
+        if let Some(child) = inner.find(|i| i.as_rule() != Rule::self_closing) {
+            return Err(closed_element_error(&element, &child).into());
         }
+    } else {
+        let text = build_inline_text(&mut inner, src);
+        element.children = text.map(Node::Text).into_iter().collect();
+        element.children.extend(build_nodes(&mut inner, src)?);
     }
-    attributes
+    Ok(element)
 }
 
-fn build_element<'a>(pairs: &mut Pairs<'a, Rule>) -> Vec<Element<'a>> {
+fn build_nodes<'a>(
+    pairs: &mut Pairs<Rule>,
+    src: &Source<'a>,
+) -> Result<Vec<Node<'a>>, Error> {
     let mut result = vec![];
     for pair in pairs {
         let rule = pair.as_rule();
 
         match rule {
             Rule::EOI => {
-                return result;
+                return Ok(result);
             }
-            Rule::comment => {
-                let element = Element {
-                    name: "".to_string(),
-                    children: vec![],
-                    attributes: vec![],
-                };
-                result.push(element);
+            Rule::tag | Rule::void_element | Rule::raw_text_tag => {
+                result.push(Node::Element(build_element(pair, src)?));
+            }
+            Rule::text => {
+                let data = Cow::Borrowed(src.str(pair.as_span()));
+                result.push(Node::Text(Text { data }));
             }
-            Rule::html | Rule::head | Rule::body => {
-                // block element
-                let name = format!("{:?}", rule);
-                let mut element = Element {
-                    name,
-                    children: vec![],
-                    attributes: vec![],
+            Rule::text_block => {
+                result.push(Node::Text(build_text_block(pair)));
+            }
+            Rule::code_comment | Rule::html_comment => {
+                let kind = if rule == Rule::html_comment {
+                    CommentKind::Html
+                } else {
+                    CommentKind::Code
                 };
+                let data = build_text_lines(pair);
+                result.push(Node::Comment(Comment::new(kind, data)));
+            }
+            Rule::conditional_comment => {
                 let mut inner = pair.into_inner();
-                element.attributes = build_attributes(&mut inner);
-                element.children = build_element(&mut inner);
-                result.push(element);
+                let condition =
+                    inner.next().map_or("", |c| src.str(c.as_span()));
+                let children = build_nodes(&mut inner, src)?;
+                result.push(Node::ConditionalComment(ConditionalComment {
+                    condition,
+                    children,
+                }));
             }
-            Rule::link => {
-                // void element
-                let mut element = Element {
-                    name: "link".to_string(),
-                    children: vec![],
-                    attributes: vec![],
-                };
+            Rule::raw_html => {
                 let mut inner = pair.into_inner();
-                element.attributes = build_attributes(&mut inner);
-                result.push(element);
+                let data = inner.next().map_or("", |l| src.str(l.as_span()));
+                let children = build_nodes(&mut inner, src)?;
+                result.push(Node::RawHtml(RawHtml { data, children }));
             }
             _ => {} // do nothing
         }
     }
-    result
+    Ok(result)
+}
+
+fn closed_element_error(
+    element: &Element,
+    child: &Pair<Rule>,
+) -> ParseError<Rule> {
+    let kind = if element.is_void() {
+        "void"
+    } else {
+        "self-closing"
+    };
+    let message = format!(
+        "{} element `{}` must not have any children",
+        kind, element.name
+    );
+    ParseError::new_from_span(
+        ErrorVariant::CustomError { message },
+        child.as_span(),
+    )
 }
 
 #[cfg(test)]
@@ -209,7 +795,7 @@ mod test {
 
     #[test]
     fn test_code_comment() {
-        let comments = vec![
+        let comments = [
             "/ foo bar baz qux quux",
             "/foo bar baz qux quux",
             "/  foo bar baz qux quux",
@@ -221,7 +807,7 @@ mod test {
 
     #[test]
     fn test_html_comment() {
-        let comments = vec![
+        let comments = [
             "/! foo bar baz qux quux",
             "/!foo bar baz qux quux",
             "/!  foo bar baz qux quux",
@@ -231,15 +817,141 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_tag() {
+        let tags = ["div", "section", "nav", "h1", "my-element"];
+        for t in tags.iter() {
+            assert_rule!(Rule::tag, t);
+        }
+    }
+
+    #[test]
+    fn test_conditional_comment() {
+        let comments = ["/[if IE]", "/[if lt IE 9]", "/[if !IE]  "];
+        for c in comments.iter() {
+            assert_rule!(Rule::conditional_comment, c);
+        }
+    }
+
+    #[test]
+    fn test_shortcut() {
+        let tags = ["p.lead", "#main", ".container", "#main.container.wide"];
+        for t in tags.iter() {
+            assert_rule!(Rule::tag, t);
+        }
+    }
+
+    #[test]
+    fn test_attribute() {
+        let attributes = [
+            r#"href="/assets/app.css""#,
+            r#"content="width=device-width, initial-scale=1""#,
+            r#"alt='A cat'"#,
+            r#"title="Say \"Hi\"""#,
+            r#"title='It\'s'"#,
+            r#"value="""#,
+        ];
+        for a in attributes.iter() {
+            assert_rule!(Rule::attribute, a);
+        }
+    }
+
+    #[test]
+    fn test_unescape() {
+        assert!(matches!(unescape("plain"), Cow::Borrowed("plain")));
+        assert_eq!(r#"Say "Hi""#, unescape(r#"Say \"Hi\""#));
+        assert_eq!("It's", unescape(r#"It\'s"#));
+        assert_eq!(r#"C:\"#, unescape(r#"C:\\"#));
+        assert_eq!("a\tb\nc", unescape(r#"a\tb\nc"#));
+        assert_eq!(r#"\d+"#, unescape(r#"\d+"#));
+        assert_eq!(r#"\"#, unescape(r#"\"#));
+    }
+
+    #[test]
+    fn test_boolean_attribute() {
+        let attributes = [
+            "checked",
+            "selected",
+            "async",
+            "defer",
+            "disabled",
+            "open=true",
+        ];
+        for a in attributes.iter() {
+            assert_rule!(Rule::attribute, a);
+        }
+        assert!(LitheParser::parse(Rule::attribute, "lead").is_err());
+        assert!(LitheParser::parse(Rule::attribute, "a=trueish").is_err());
+
+        let attributes = ["checked", "data-x", "checked=true"];
+        for a in attributes.iter() {
+            assert_rule!(Rule::wrapped_attribute, a);
+        }
+    }
+
+    #[test]
+    fn test_attribute_wrapper() {
+        let tags = [
+            r#"a(href="/" class="x")"#,
+            r#"img[src="a.png" alt="b"]"#,
+            r#"meta{name="x"}"#,
+            r#"input( type="checkbox" checked )"#,
+            "a(\n  href=\"/\"\n  class=\"x\")",
+            "p()",
+        ];
+        for t in tags.iter() {
+            assert_rule!(Rule::tag, t);
+        }
+    }
+
+    #[test]
+    fn test_inline_tag() {
+        let tags = [
+            "ul: li",
+            r#"ul: li: a href="/" Home"#,
+            r#"ul.nav: li(class="x"): a(href="/")"#,
+            "p: br",
+        ];
+        for t in tags.iter() {
+            assert_rule!(Rule::tag, t);
+        }
+    }
+
+    #[test]
+    fn test_whitespace_control() {
+        let tags = ["a>", "a<", "a<>", r#"a.btn> href="/""#, "img<>(src='a')"];
+        for t in tags.iter() {
+            assert_rule!(Rule::tag, t);
+        }
+    }
+
+    #[test]
+    fn test_self_closing() {
+        let tags = ["img/", "my-icon/", r#"my-icon name="star" /"#, "x(a)/"];
+        for t in tags.iter() {
+            assert_rule!(Rule::tag, t);
+        }
+    }
+
+    #[test]
+    fn test_raw_html() {
+        let lines =
+            ["<div>", r#"<script async src="a.js"></script>"#, "</div>"];
+        for l in lines.iter() {
+            assert_rule!(Rule::raw_html, l);
+        }
+    }
+
     #[test]
     fn test_doctype() {
-        let doctypes = vec![
+        let doctypes = [
             "doctype xml",
             "doctype xml ISO-8859-1",
             "doctype html",
             "doctype  5",
             "doctype\n1.1",
             "doctype\n\n\n strict",
+            r#"doctype html PUBLIC "-//W3C//DTD HTML 4.01//EN""#,
         ];
         for d in doctypes.iter() {
             assert_rule!(Rule::doctype, d);
@@ -256,26 +968,38 @@ mod test {
         assert!(parse("doctype  xml").is_ok());
         assert!(parse("doctype xml ISO-8859-1").is_ok());
 
-        // TODO: mode
-
         // xhtml mode
-        assert!(parse("doctype html").is_ok());
-        assert!(parse("doctype 5").is_ok());
-        assert!(parse("doctype 1.1").is_ok());
-        assert!(parse("doctype strict").is_ok());
-        assert!(parse("doctype frameset").is_ok());
-        assert!(parse("doctype mobile").is_ok());
-        assert!(parse("doctype basic").is_ok());
-        assert!(parse("doctype transitional").is_ok());
+        let options = ParseOptions {
+            format: Some(Format::Xhtml),
+            ..Default::default()
+        };
+        for name in &[
+            "html",
+            "5",
+            "1.1",
+            "strict",
+            "frameset",
+            "mobile",
+            "basic",
+            "transitional",
+        ] {
+            let s = format!("doctype {}", name);
+            assert!(parse_with_options(&s, &options).is_ok());
+        }
 
         // html mode
-        assert!(parse("doctype html").is_ok());
-        assert!(parse("doctype 5").is_ok());
-        assert!(parse("doctype strict").is_ok());
-        assert!(parse("doctype frameset").is_ok());
-        assert!(parse("doctype transitional").is_ok());
+        let options = ParseOptions {
+            format: Some(Format::Html),
+            ..Default::default()
+        };
+        for name in &["html", "5", "strict", "frameset", "transitional"] {
+            let s = format!("doctype {}", name);
+            assert!(parse_with_options(&s, &options).is_ok());
+        }
+        assert!(parse_with_options("doctype 1.1", &options).is_err());
 
-        assert!(parse("doctype unknown").is_err());
+        let err = parse("doctype unknown").unwrap_err();
+        assert!(err.to_string().contains("unknown doctype `unknown`"));
     }
 
     #[test]
@@ -293,9 +1017,152 @@ mod test {
         assert_eq!("", doctype.public_id);
         assert_eq!("", doctype.system_id);
 
+        let comment = doc.children[0].as_comment().unwrap();
+        assert_eq!(CommentKind::Code, comment.kind);
+        assert_eq!("Comment", comment.data);
+
+        let comment = doc.children[1].as_comment().unwrap();
+        assert_eq!(CommentKind::Html, comment.kind);
+        assert_eq!("Das ist ein Test", comment.data);
+
+        assert!(doc.children.iter().all(|n| n.as_element().is_none()));
+    }
+
+    #[test]
+    fn test_parse_doctype_mode() {
+        // inferred
+        let doctype = parse("doctype strict").unwrap().r#type.unwrap();
+        assert_eq!(Format::Html, doctype.format());
+        assert_eq!("-//W3C//DTD HTML 4.01//EN", doctype.public_id);
+
+        let doctype = parse("doctype 1.1").unwrap().r#type.unwrap();
+        assert_eq!(Format::Xhtml, doctype.format());
+        assert_eq!("-//W3C//DTD XHTML 1.1//EN", doctype.public_id);
+
+        // explicit
+        let options = ParseOptions {
+            format: Some(Format::Xhtml),
+            ..Default::default()
+        };
+        let doc = parse_with_options("doctype strict", &options).unwrap();
+        let doctype = doc.r#type.unwrap();
+        assert_eq!(Format::Xhtml, doctype.format());
+        assert_eq!("-//W3C//DTD XHTML 1.0 Strict//EN", doctype.public_id);
+        assert_eq!(
+            "http://www.w3.org/TR/xhtml1/DTD/xhtml1-strict.dtd",
+            doctype.system_id
+        );
+
+        let options = ParseOptions {
+            format: Some(Format::Html),
+            ..Default::default()
+        };
+        let err = parse_with_options("doctype mobile", &options).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("doctype `mobile` is not available in html mode"));
+    }
+
+    #[test]
+    fn test_parse_raw_doctype() {
+        let doc = parse(
+            r#"doctype html PUBLIC "-//W3C//DTD XHTML+RDFa 1.0//EN" "http://www.w3.org/MarkUp/DTD/xhtml-rdfa-1.dtd"
+html
+"#,
+        )
+        .unwrap();
+        let doctype = doc.r#type.unwrap();
+        assert_eq!("html", doctype.name);
+        assert_eq!(Format::Xhtml, doctype.format());
+        assert_eq!(
+            Some(
+                r#"html PUBLIC "-//W3C//DTD XHTML+RDFa 1.0//EN" "http://www.w3.org/MarkUp/DTD/xhtml-rdfa-1.dtd""#
+                    .into()
+            ),
+            doctype.declaration
+        );
+        assert_eq!("html", doc.children[0].as_element().unwrap().name);
+
+        let doc = parse("doctype svg SYSTEM \"svg11.dtd\"  \n").unwrap();
+        let doctype = doc.r#type.unwrap();
+        assert_eq!("svg", doctype.name);
+        assert_eq!(
+            Some(r#"svg SYSTEM "svg11.dtd""#.into()),
+            doctype.declaration
+        );
+    }
+
+    #[test]
+    fn test_parse_registered_doctype() {
+        let mut options = ParseOptions::default();
+        options
+            .register_doctype("legacy", r#"html SYSTEM "about:legacy-compat""#)
+            .register_doctype("html", "HTML");
+
+        let doc = parse_with_options("doctype legacy\np", &options).unwrap();
+        let doctype = doc.r#type.unwrap();
+        assert_eq!("legacy", doctype.name);
+        assert_eq!(
+            Some(r#"html SYSTEM "about:legacy-compat""#.into()),
+            doctype.declaration
+        );
+
+        // registered ones are looked up first
+        let doc = parse_with_options("doctype html", &options).unwrap();
+        assert_eq!(Some("HTML".into()), doc.r#type.unwrap().declaration);
+
+        let doc = parse_with_options("doctype strict", &options).unwrap();
+        assert_eq!(None, doc.r#type.unwrap().declaration);
+
+        assert!(parse("doctype legacy").is_err());
+    }
+
+    #[test]
+    fn test_parse_without_doctype() {
+        let doc = parse("p Hello\n/ note\n").unwrap();
+        assert!(doc.r#type.is_none());
+        assert_eq!(2, doc.children.len());
+        assert_eq!("p", doc.children[0].as_element().unwrap().name);
+
+        let doc = parse("").unwrap();
+        assert!(doc.r#type.is_none());
         assert!(doc.children.is_empty());
     }
 
+    #[test]
+    fn test_parse_fragment() {
+        let doc = parse_fragment(
+            r#"
+li.active
+  a href="/" Home
+| text
+"#,
+        )
+        .unwrap();
+        assert!(doc.r#type.is_none());
+        let li = doc.children[0].as_element().unwrap();
+        assert_eq!("li", li.name);
+        assert_eq!("a", li.children[0].as_element().unwrap().name);
+        assert_eq!("text", doc.children[1].as_text().unwrap().data);
+
+        let err = parse_fragment("doctype html\np\n").unwrap_err();
+        assert!(err.to_string().contains("fragment must not have a doctype"));
+    }
+
+    #[test]
+    fn test_parse_xml_doctype() {
+        let doc = parse("doctype xml\n").unwrap();
+        let doctype = doc.r#type.unwrap();
+        assert_eq!("xml", doctype.name);
+        assert_eq!(None, doctype.encoding);
+
+        let doc = parse("doctype xml ISO-8859-1\nfeed\n").unwrap();
+        let doctype = doc.r#type.unwrap();
+        assert_eq!("xml", doctype.name);
+        assert_eq!(Some("ISO-8859-1"), doctype.encoding);
+        assert_eq!("feed", doc.children[0].as_element().unwrap().name);
+    }
+
     #[test]
     fn test_parse_html_tag() {
         let doc = parse(
@@ -305,7 +1172,7 @@ html
         )
         .unwrap();
 
-        let html = &doc.children[0];
+        let html = doc.children[0].as_element().unwrap();
         assert_eq!("html", html.name);
 
         assert!(html.children.is_empty());
@@ -321,14 +1188,14 @@ html lang="en"
         )
         .unwrap();
 
-        let html = &doc.children[0];
+        let html = doc.children[0].as_element().unwrap();
         assert_eq!("html", html.name);
 
         assert!(html.children.is_empty());
 
         let attr = &html.attributes[0];
         assert_eq!("lang", attr.name);
-        assert_eq!("en", attr.value);
+        assert_eq!(Some("en"), attr.value.as_deref());
     }
 
     #[test]
@@ -343,39 +1210,950 @@ html
         )
         .unwrap();
 
-        let doctype = DocumentType::new("html", "html");
+        let doctype = DocumentType::new(Spec::Html, "html").unwrap();
         let expected = Document {
             r#type: Some(doctype),
-            children: vec![Element {
+            children: vec![Node::Element(Element {
                 name: "html".to_string(),
                 attributes: vec![],
                 children: vec![
-                    Element {
+                    Node::Element(Element {
                         name: "head".to_string(),
                         attributes: vec![],
-                        children: vec![Element {
+                        children: vec![Node::Element(Element {
                             name: "link".to_string(),
                             attributes: vec![
                                 Attr {
                                     name: "rel",
-                                    value: "stylesheet",
+                                    value: Some("stylesheet".into()),
                                 },
                                 Attr {
                                     name: "href",
-                                    value: "style.css",
+                                    value: Some("style.css".into()),
                                 },
                             ],
                             children: vec![],
-                        }],
-                    },
-                    Element {
+                            ..Default::default()
+                        })],
+                        ..Default::default()
+                    }),
+                    Node::Element(Element {
                         name: "body".to_string(),
                         attributes: vec![],
                         children: vec![],
-                    },
+                        ..Default::default()
+                    }),
                 ],
-            }],
+                ..Default::default()
+            })],
         };
         assert_eq!(expected, doc);
     }
+
+    #[test]
+    fn test_parse_nested_tags() {
+        let doc = parse(
+            r#"doctype html
+html
+  body
+    nav
+      ul
+        li
+          a href="index.html"
+        li
+    section id="main"
+      p
+  footer
+"#,
+        )
+        .unwrap();
+
+        let html = doc.children[0].as_element().unwrap();
+        assert_eq!("html", html.name);
+        assert_eq!(2, html.children.len());
+
+        let body = html.children[0].as_element().unwrap();
+        assert_eq!("body", body.name);
+        assert_eq!(2, body.children.len());
+
+        let nav = body.children[0].as_element().unwrap();
+        assert_eq!("nav", nav.name);
+
+        let ul = nav.children[0].as_element().unwrap();
+        assert_eq!("ul", ul.name);
+        assert_eq!(2, ul.children.len());
+
+        let a = ul.children[0].as_element().unwrap().children[0]
+            .as_element()
+            .unwrap();
+        assert_eq!("a", a.name);
+        assert_eq!("href", a.attributes[0].name);
+        assert_eq!(Some("index.html"), a.attributes[0].value.as_deref());
+        assert!(ul.children[1].as_element().unwrap().children.is_empty());
+
+        let section = body.children[1].as_element().unwrap();
+        assert_eq!("section", section.name);
+        assert_eq!("id", section.attributes[0].name);
+        assert_eq!(Some("main"), section.attributes[0].value.as_deref());
+        assert_eq!("p", section.children[0].as_element().unwrap().name);
+
+        assert_eq!("footer", html.children[1].as_element().unwrap().name);
+    }
+
+    #[test]
+    fn test_parse_invalid_indentation() {
+        assert!(parse("doctype html\nlink\n  body\n").is_err());
+        assert!(parse("doctype html\nhtml\n\tbody\n  p\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_indent_widths() {
+        let doc = parse(
+            r#"doctype html
+html
+    head
+        title
+    body
+     div
+         p
+     div
+"#,
+        )
+        .unwrap();
+
+        let html = doc.children[0].as_element().unwrap();
+        assert_eq!(2, html.children.len());
+        let body = html.children[1].as_element().unwrap();
+        assert_eq!(2, body.children.len());
+        let div = body.children[0].as_element().unwrap();
+        assert_eq!("p", div.children[0].as_element().unwrap().name);
+    }
+
+    #[test]
+    fn test_parse_tabs() {
+        let doc =
+            parse("doctype html\nhtml\n\thead\n\t\ttitle\n\tbody\n").unwrap();
+        let html = doc.children[0].as_element().unwrap();
+        let head = html.children[0].as_element().unwrap();
+        assert_eq!("title", head.children[0].as_element().unwrap().name);
+        assert_eq!("body", html.children[1].as_element().unwrap().name);
+
+        let s = expand_tabs("doctype html\nhtml\n\thead\n    body\n", 4);
+        let doc = parse(&s).unwrap();
+        let html = doc.children[0].as_element().unwrap();
+        assert_eq!(2, html.children.len());
+
+        let s = expand_tabs("doctype html\nhtml\n\thead\n    body\n", 2);
+        let doc = parse(&s).unwrap();
+        let html = doc.children[0].as_element().unwrap();
+        let head = html.children[0].as_element().unwrap();
+        assert_eq!("body", head.children[0].as_element().unwrap().name);
+    }
+
+    #[test]
+    fn test_parse_with_tab_width() {
+        let s = "doctype html
+html
+\thead
+\t\tmeta charset=\"utf-8\"
+\t\tp Lithe
+    body
+\t\ta href=\"/\" class=\"x\" Home
+";
+        assert!(parse(s).is_err());
+
+        let options = ParseOptions {
+            tab_width: Some(4),
+            ..Default::default()
+        };
+        let doc = parse_with_options(s, &options).unwrap();
+        assert_eq!("html", doc.r#type.as_ref().unwrap().name);
+        let html = doc.children[0].as_element().unwrap();
+        assert_eq!(2, html.children.len());
+
+        let body = html.children[1].as_element().unwrap();
+        let a = body.children[0].as_element().unwrap();
+        assert_eq!(
+            vec![Attr::new("href", "/"), Attr::new("class", "x")],
+            a.attributes
+        );
+        assert_eq!("Home", a.children[0].as_text().unwrap().data);
+
+        // borrowed from the original template
+        let range = s.as_bytes().as_ptr_range();
+        assert!(range.contains(&a.attributes[1].name.as_ptr()));
+        let head = html.children[0].as_element().unwrap();
+        let p = head.children[1].as_element().unwrap();
+        let text = &p.children[0].as_text().unwrap().data;
+        assert!(matches!(text, Cow::Borrowed(_)));
+        assert!(range.contains(&text.as_ptr()));
+
+        let doc = parse_fragment_with_options("ul\n\tli a\n    li b", &options)
+            .unwrap();
+        assert_eq!(2, doc.children[0].as_element().unwrap().children.len());
+        assert!(parse_fragment_with_options("doctype html", &options).is_err());
+
+        // shortcuts and an xml prolog aren't taken from the template
+        let doc = parse_with_options(".a\n\tp", &options).unwrap();
+        let div = doc.children[0].as_element().unwrap();
+        assert_eq!(vec![Attr::new("class", "a")], div.attributes);
+        assert_eq!("p", div.children[0].as_element().unwrap().name);
+
+        let doc =
+            parse_with_options("div#x\n\tp.y z\n\t\tspan", &options).unwrap();
+        let div = doc.children[0].as_element().unwrap();
+        assert_eq!(vec![Attr::new("id", "x")], div.attributes);
+        let p = div.children[0].as_element().unwrap();
+        assert_eq!(vec![Attr::new("class", "y")], p.attributes);
+        assert_eq!("z", p.children[0].as_text().unwrap().data);
+        assert_eq!("span", p.children[1].as_element().unwrap().name);
+
+        let doc =
+            parse_with_options("doctype xml utf-8\nfeed\n\tentry", &options)
+                .unwrap();
+        let prolog = doc.r#type.as_ref().unwrap();
+        assert_eq!("xml", prolog.name);
+        assert_eq!(Some("utf-8"), prolog.encoding);
+        let feed = doc.children[0].as_element().unwrap();
+        assert_eq!("entry", feed.children[0].as_element().unwrap().name);
+
+        let doc = parse_with_options(
+            "/[if IE]\n\t<div>\n\t\tp a\n\t</div>",
+            &options,
+        )
+        .unwrap();
+        let comment = doc.children[0].as_conditional_comment().unwrap();
+        assert_eq!("if IE", comment.condition);
+        let raw = comment.children[0].as_raw_html().unwrap();
+        assert_eq!("<div>", raw.data);
+        let p = raw.children[0].as_element().unwrap();
+        assert_eq!("a", p.children[0].as_text().unwrap().data);
+    }
+
+    #[test]
+    fn test_expand_tabs() {
+        assert!(matches!(expand_tabs("a\n  b", 2), Cow::Borrowed(_)));
+        assert_eq!("a\n    b\tc\n  d", expand_tabs("a\n\t\tb\tc\n\td", 2));
+    }
+
+    #[test]
+    fn test_parse_inconsistent_dedent() {
+        let err = parse(
+            r#"doctype html
+div
+    p
+      span
+   a
+"#,
+        )
+        .unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("5:4"));
+        assert!(message.contains("inconsistent indentation at line 5"));
+
+        let err = parse("doctype html\ndiv\n  p\n    | a\n        b\n c\n")
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("inconsistent indentation at line 6"));
+
+        // not an indentation error
+        let err = parse("doctype html\ndiv\n  p\n  ?\n").unwrap_err();
+        assert!(!err.to_string().contains("inconsistent indentation"));
+    }
+
+    #[test]
+    fn test_parse_void_elements() {
+        let doc = parse(
+            r#"doctype html
+html
+  head
+    base href="index.html"
+    meta charset="utf-8"
+    link rel="stylesheet" href="style.css"
+  body
+    br
+    hr
+    img src="logo.png"
+    input type="text" name="q"
+    area
+    col
+    embed
+    source
+    track
+    wbr
+"#,
+        )
+        .unwrap();
+
+        let html = doc.children[0].as_element().unwrap();
+        let head = html.children[0].as_element().unwrap();
+        let head = head.children.iter().map(|n| n.as_element().unwrap());
+        let children = head.collect::<Vec<_>>();
+        let names = children.iter().map(|e| e.name.as_str());
+        assert_eq!(vec!["base", "meta", "link"], names.collect::<Vec<_>>());
+        assert_eq!("charset", children[1].attributes[0].name);
+        assert_eq!(Some("utf-8"), children[1].attributes[0].value.as_deref());
+
+        let body = html.children[1].as_element().unwrap();
+        let body = body.children.iter().map(|n| n.as_element().unwrap());
+        let children = body.collect::<Vec<_>>();
+        let names = children.iter().map(|e| e.name.as_str());
+        assert_eq!(
+            vec![
+                "br", "hr", "img", "input", "area", "col", "embed", "source",
+                "track", "wbr"
+            ],
+            names.collect::<Vec<_>>()
+        );
+        assert_eq!(2, children[3].attributes.len());
+        assert!(children.iter().all(|e| e.children.is_empty()));
+    }
+
+    #[test]
+    fn test_parse_void_element_with_children() {
+        let err = parse(
+            r#"doctype html
+html
+  body
+    br
+      p
+"#,
+        )
+        .unwrap_err();
+
+        let message = err.to_string();
+        assert!(message.contains("5:7"));
+        assert!(
+            message.contains("void element `br` must not have any children")
+        );
+    }
+
+    #[test]
+    fn test_parse_text() {
+        let doc = parse(
+            r#"doctype html
+p Hello world
+p
+  | Fish & Chips
+p
+"#,
+        )
+        .unwrap();
+
+        let p = doc.children[0].as_element().unwrap();
+        assert_eq!("Hello world", p.children[0].as_text().unwrap().data);
+
+        let p = doc.children[1].as_element().unwrap();
+        assert_eq!("Fish & Chips", p.children[0].as_text().unwrap().data);
+
+        let p = doc.children[2].as_element().unwrap();
+        assert!(p.children.is_empty());
+    }
+
+    #[test]
+    fn test_parse_text_block() {
+        let doc = parse(
+            r#"doctype html
+p
+  | This line is on the left margin.
+     This line will have one space in front of it.
+
+    This line is also on the left margin.
+p
+  |
+    This line is on the left margin.
+      This line will have two spaces in front of it.
+p
+  ' Trailing
+p
+  | a
+  | b
+"#,
+        )
+        .unwrap();
+
+        let text = |i: usize| {
+            let p = doc.children[i].as_element().unwrap();
+            p.children
+                .iter()
+                .map(|n| n.as_text().unwrap().data.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            vec![
+                "This line is on the left margin.
+ This line will have one space in front of it.
+
+This line is also on the left margin."
+            ],
+            text(0)
+        );
+        assert_eq!(
+            vec![
+                "This line is on the left margin.
+  This line will have two spaces in front of it."
+            ],
+            text(1)
+        );
+        assert_eq!(vec!["Trailing "], text(2));
+        assert_eq!(vec!["a", "b"], text(3));
+    }
+
+    #[test]
+    fn test_parse_multiline_comments() {
+        let doc = parse(
+            r#"doctype html
+/! First line
+   Second line
+     Indented line
+/
+  Code comment
+  spans lines
+p
+"#,
+        )
+        .unwrap();
+
+        let comment = doc.children[0].as_comment().unwrap();
+        assert_eq!(CommentKind::Html, comment.kind);
+        assert_eq!("First line\nSecond line\n  Indented line", comment.data);
+
+        let comment = doc.children[1].as_comment().unwrap();
+        assert_eq!(CommentKind::Code, comment.kind);
+        assert_eq!("Code comment\nspans lines", comment.data);
+
+        assert_eq!("p", doc.children[2].as_element().unwrap().name);
+    }
+
+    #[test]
+    fn test_parse_conditional_comment() {
+        let doc = parse(
+            r#"doctype html
+head
+  /[if lt IE 9]
+    script src="html5shiv.js"
+    p
+      | Get a better browser.
+  /[if IE]
+"#,
+        )
+        .unwrap();
+
+        let head = doc.children[0].as_element().unwrap();
+
+        let comment = head.children[0].as_conditional_comment().unwrap();
+        assert_eq!("if lt IE 9", comment.condition);
+        assert_eq!(2, comment.children.len());
+
+        let script = comment.children[0].as_element().unwrap();
+        assert_eq!("script", script.name);
+        assert_eq!(Some("html5shiv.js"), script.attributes[0].value.as_deref());
+
+        let p = comment.children[1].as_element().unwrap();
+        let text = p.children[0].as_text().unwrap();
+        assert_eq!("Get a better browser.", text.data);
+
+        let comment = head.children[1].as_conditional_comment().unwrap();
+        assert_eq!("if IE", comment.condition);
+        assert!(comment.children.is_empty());
+    }
+
+    #[test]
+    fn test_parse_shortcuts() {
+        let doc = parse(
+            r#"doctype html
+#main.container.wide
+  p.lead Hello
+  .note.small class="warning" title="note"
+  img.logo#logo src="logo.png"
+"#,
+        )
+        .unwrap();
+
+        let div = doc.children[0].as_element().unwrap();
+        assert_eq!("div", div.name);
+        assert_eq!(
+            vec![
+                Attr::new("id", "main"),
+                Attr::new("class", "container wide")
+            ],
+            div.attributes
+        );
+
+        let p = div.children[0].as_element().unwrap();
+        assert_eq!("p", p.name);
+        assert_eq!(vec![Attr::new("class", "lead")], p.attributes);
+        assert_eq!("Hello", p.children[0].as_text().unwrap().data);
+
+        let note = div.children[1].as_element().unwrap();
+        assert_eq!("div", note.name);
+        assert_eq!(
+            vec![
+                Attr::new("class", "note small warning"),
+                Attr::new("title", "note"),
+            ],
+            note.attributes
+        );
+
+        let img = div.children[2].as_element().unwrap();
+        assert_eq!("img", img.name);
+        assert_eq!(
+            vec![
+                Attr::new("class", "logo"),
+                Attr::new("id", "logo"),
+                Attr::new("src", "logo.png"),
+            ],
+            img.attributes
+        );
+    }
+
+    #[test]
+    fn test_parse_attribute_values() {
+        let doc = parse(
+            r#"doctype html
+head
+  meta name="viewport" content="width=device-width, initial-scale=1"
+  link rel="stylesheet" href="/assets/app.css"
+body
+  img src='cat.png' alt="A \"cat\"" title='It\'s a cat'
+"#,
+        )
+        .unwrap();
+
+        let head = doc.children[0].as_element().unwrap();
+        let meta = head.children[0].as_element().unwrap();
+        assert_eq!(
+            vec![
+                Attr::new("name", "viewport"),
+                Attr::new("content", "width=device-width, initial-scale=1"),
+            ],
+            meta.attributes
+        );
+        let link = head.children[1].as_element().unwrap();
+        assert_eq!(
+            Some("/assets/app.css"),
+            link.attributes[1].value.as_deref()
+        );
+
+        let body = doc.children[1].as_element().unwrap();
+        let img = body.children[0].as_element().unwrap();
+        assert_eq!(
+            vec![
+                Attr::new("src", "cat.png"),
+                Attr::new("alt", r#"A "cat""#),
+                Attr::new("title", "It's a cat"),
+            ],
+            img.attributes
+        );
+        assert!(matches!(img.attributes[0].value, Some(Cow::Borrowed(_))));
+    }
+
+    #[test]
+    fn test_parse_boolean_attributes() {
+        let doc = parse(
+            r#"doctype html
+input type="checkbox" checked
+script async defer src="app.js"
+select
+  option selected Rust
+p hidden text
+p required fields are marked
+li default settings
+details open=true
+"#,
+        )
+        .unwrap();
+
+        let input = doc.children[0].as_element().unwrap();
+        assert_eq!(
+            vec![Attr::new("type", "checkbox"), Attr::boolean("checked")],
+            input.attributes
+        );
+
+        let script = doc.children[1].as_element().unwrap();
+        assert_eq!(
+            vec![
+                Attr::boolean("async"),
+                Attr::boolean("defer"),
+                Attr::new("src", "app.js"),
+            ],
+            script.attributes
+        );
+
+        let select = doc.children[2].as_element().unwrap();
+        let option = select.children[0].as_element().unwrap();
+        assert_eq!(vec![Attr::boolean("selected")], option.attributes);
+        assert_eq!("Rust", option.children[0].as_text().unwrap().data);
+
+        let p = doc.children[3].as_element().unwrap();
+        assert_eq!(vec![Attr::boolean("hidden")], p.attributes);
+        assert_eq!("text", p.children[0].as_text().unwrap().data);
+
+        let p = doc.children[4].as_element().unwrap();
+        assert!(p.attributes.is_empty());
+        assert_eq!(
+            "required fields are marked",
+            p.children[0].as_text().unwrap().data
+        );
+
+        let li = doc.children[5].as_element().unwrap();
+        assert!(li.attributes.is_empty());
+        assert_eq!("default settings", li.children[0].as_text().unwrap().data);
+
+        let details = doc.children[6].as_element().unwrap();
+        assert_eq!(vec![Attr::boolean("open")], details.attributes);
+    }
+
+    #[test]
+    fn test_parse_attribute_wrappers() {
+        let doc = parse(
+            r#"doctype html
+head
+  meta{name="x" content="y"}
+body
+  a.btn(href="/" class="x") Home
+  img[src="a.png" alt="b"]
+  input(type="checkbox"
+        name="agree"
+        data-value='1' checked)
+  p Done
+"#,
+        )
+        .unwrap();
+
+        let head = doc.children[0].as_element().unwrap();
+        let meta = head.children[0].as_element().unwrap();
+        assert_eq!(
+            vec![Attr::new("name", "x"), Attr::new("content", "y")],
+            meta.attributes
+        );
+
+        let body = doc.children[1].as_element().unwrap();
+        let a = body.children[0].as_element().unwrap();
+        assert_eq!(
+            vec![Attr::new("class", "btn x"), Attr::new("href", "/")],
+            a.attributes
+        );
+        assert_eq!("Home", a.children[0].as_text().unwrap().data);
+
+        let img = body.children[1].as_element().unwrap();
+        assert_eq!(
+            vec![Attr::new("src", "a.png"), Attr::new("alt", "b")],
+            img.attributes
+        );
+
+        let input = body.children[2].as_element().unwrap();
+        assert_eq!(
+            vec![
+                Attr::new("type", "checkbox"),
+                Attr::new("name", "agree"),
+                Attr::new("data-value", "1"),
+                Attr::boolean("checked"),
+            ],
+            input.attributes
+        );
+
+        let p = body.children[3].as_element().unwrap();
+        assert_eq!("p", p.name);
+    }
+
+    #[test]
+    fn test_parse_unclosed_attribute_wrapper() {
+        let err = parse(
+            r#"doctype html
+body
+  a(href="/"
+    class="x"
+  p
+"#,
+        )
+        .unwrap_err();
+
+        let message = err.to_string();
+        assert!(message.contains("3:4"));
+        assert!(message.contains("attribute wrapper is not closed"));
+
+        let err = parse("doctype html\nimg[src=\"a.png\"").unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("2:4"));
+        assert!(message.contains("(expected `]`)"));
+
+        // closed, but with an invalid attribute
+        let err = parse("a(href=/x) y\np z").unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("1:8"));
+        assert!(message.contains("expected attr_value"));
+        assert!(!message.contains("not closed"));
+
+        let err = parse(r#"a(title=")" y"#).unwrap_err();
+        assert!(err.to_string().contains("(expected `)`)"));
+
+        // a closing symbol in the text after it
+        let err = parse("a(href=\"/\"\np Hello (world)").unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("1:2"));
+        assert!(message.contains("attribute wrapper is not closed"));
+    }
+
+    #[test]
+    fn test_parse_inline_tags() {
+        let doc = parse(
+            r#"doctype html
+nav
+  ul.menu: li: a href="/" Home
+  ul: li.active: a(href="/about")
+    span About
+  p: img src="logo.png"
+"#,
+        )
+        .unwrap();
+
+        let nav = doc.children[0].as_element().unwrap();
+
+        let ul = nav.children[0].as_element().unwrap();
+        assert_eq!("ul", ul.name);
+        assert_eq!(vec![Attr::new("class", "menu")], ul.attributes);
+        let li = ul.children[0].as_element().unwrap();
+        assert_eq!("li", li.name);
+        let a = li.children[0].as_element().unwrap();
+        assert_eq!("a", a.name);
+        assert_eq!(vec![Attr::new("href", "/")], a.attributes);
+        assert_eq!("Home", a.children[0].as_text().unwrap().data);
+
+        let ul = nav.children[1].as_element().unwrap();
+        let li = ul.children[0].as_element().unwrap();
+        assert_eq!(vec![Attr::new("class", "active")], li.attributes);
+        let a = li.children[0].as_element().unwrap();
+        assert_eq!(vec![Attr::new("href", "/about")], a.attributes);
+        let span = a.children[0].as_element().unwrap();
+        assert_eq!("span", span.name);
+        assert_eq!("About", span.children[0].as_text().unwrap().data);
+
+        let p = nav.children[2].as_element().unwrap();
+        let img = p.children[0].as_element().unwrap();
+        assert_eq!("img", img.name);
+        assert_eq!(vec![Attr::new("src", "logo.png")], img.attributes);
+    }
+
+    #[test]
+    fn test_parse_whitespace_control() {
+        let doc = parse(
+            r#"doctype html
+p
+  a> href="/" Home
+  a< href="/about" About
+  a<>(href="/blog") Blog
+  img.icon> src="icon.png"
+  span
+"#,
+        )
+        .unwrap();
+
+        let p = doc.children[0].as_element().unwrap();
+        let flags = p
+            .children
+            .iter()
+            .map(|n| n.as_element().unwrap())
+            .map(|e| (e.leading_whitespace, e.trailing_whitespace))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                (false, true),
+                (true, false),
+                (true, true),
+                (false, true),
+                (false, false),
+            ],
+            flags
+        );
+
+        let a = p.children[2].as_element().unwrap();
+        assert_eq!(vec![Attr::new("href", "/blog")], a.attributes);
+        assert_eq!("Blog", a.children[0].as_text().unwrap().data);
+
+        let img = p.children[3].as_element().unwrap();
+        assert_eq!(
+            vec![Attr::new("class", "icon"), Attr::new("src", "icon.png")],
+            img.attributes
+        );
+    }
+
+    #[test]
+    fn test_parse_self_closing_tags() {
+        let doc = parse(
+            r#"doctype html
+p
+  my-icon/
+  my-icon name="star" /
+  img src="a.png"/
+  span / not closed
+"#,
+        )
+        .unwrap();
+
+        let p = doc.children[0].as_element().unwrap();
+
+        let icon = p.children[0].as_element().unwrap();
+        assert_eq!("my-icon", icon.name);
+        assert!(icon.self_closing);
+
+        let icon = p.children[1].as_element().unwrap();
+        assert!(icon.self_closing);
+        assert_eq!(vec![Attr::new("name", "star")], icon.attributes);
+
+        let img = p.children[2].as_element().unwrap();
+        assert!(img.self_closing);
+        assert_eq!(vec![Attr::new("src", "a.png")], img.attributes);
+
+        let span = p.children[3].as_element().unwrap();
+        assert!(!span.self_closing);
+        assert_eq!("/ not closed", span.children[0].as_text().unwrap().data);
+    }
+
+    #[test]
+    fn test_parse_self_closing_tag_with_children() {
+        let err = parse(
+            r#"doctype html
+my-icon/
+  span
+"#,
+        )
+        .unwrap_err();
+
+        let message = err.to_string();
+        assert!(message.contains("3:3"));
+        assert!(message.contains(
+            "self-closing element `my-icon` must not have any children"
+        ));
+    }
+
+    #[test]
+    fn test_parse_raw_text_elements() {
+        let doc = parse(
+            r#"doctype html
+head
+  title Lithe & co
+  style
+    p > a {
+      color: red;
+    }
+  script src="/app.js"
+body
+  script type="module" const a = 1;
+    if (a < 2) {
+
+      console.log("p | a");
+    }
+  pre.code
+      indented
+    line
+  textarea name="note"
+    div
+      / not a comment
+  p: script var b;
+"#,
+        )
+        .unwrap();
+
+        let head = doc.children[0].as_element().unwrap();
+        let title = head.children[0].as_element().unwrap();
+        assert_eq!("Lithe & co", title.children[0].as_text().unwrap().data);
+
+        let style = head.children[1].as_element().unwrap();
+        assert_eq!(
+            "p > a {\n  color: red;\n}",
+            style.children[0].as_text().unwrap().data
+        );
+
+        let script = head.children[2].as_element().unwrap();
+        assert_eq!(vec![Attr::new("src", "/app.js")], script.attributes);
+        assert!(script.children.is_empty());
+
+        let body = doc.children[1].as_element().unwrap();
+        let script = body.children[0].as_element().unwrap();
+        assert_eq!(1, script.children.len());
+        assert_eq!(
+            "const a = 1;\nif (a < 2) {\n\n  console.log(\"p | a\");\n}",
+            script.children[0].as_text().unwrap().data
+        );
+
+        let pre = body.children[1].as_element().unwrap();
+        assert_eq!(vec![Attr::new("class", "code")], pre.attributes);
+        assert_eq!("  indented\nline", pre.children[0].as_text().unwrap().data);
+
+        let textarea = body.children[2].as_element().unwrap();
+        assert_eq!(
+            "div\n  / not a comment",
+            textarea.children[0].as_text().unwrap().data
+        );
+
+        let p = body.children[3].as_element().unwrap();
+        let script = p.children[0].as_element().unwrap();
+        assert_eq!("var b;", script.children[0].as_text().unwrap().data);
+    }
+
+    #[test]
+    fn test_parse_raw_text_tag_fallback() {
+        // an inline tag is still a tag
+        let doc = parse("pre: code x").unwrap();
+        let pre = doc.children[0].as_element().unwrap();
+        let code = pre.children[0].as_element().unwrap();
+        assert_eq!("code", code.name);
+        assert_eq!("x", code.children[0].as_text().unwrap().data);
+
+        // but the body is not
+        let doc = parse("pre\n  code\n    |   a\n").unwrap();
+        let pre = doc.children[0].as_element().unwrap();
+        assert_eq!("code\n  |   a", pre.children[0].as_text().unwrap().data);
+
+        let doc = parse("script/\nstyle(media=\"print\") /").unwrap();
+        let script = doc.children[0].as_element().unwrap();
+        assert!(script.self_closing);
+        assert!(script.children.is_empty());
+        let style = doc.children[1].as_element().unwrap();
+        assert!(style.self_closing);
+
+        let doc = parse("textarea: span x").unwrap();
+        let textarea = doc.children[0].as_element().unwrap();
+        assert_eq!("span", textarea.children[0].as_element().unwrap().name);
+
+        // title is a normal element
+        let doc = parse("title\n  | Foo").unwrap();
+        let title = doc.children[0].as_element().unwrap();
+        assert_eq!("Foo", title.children[0].as_text().unwrap().data);
+    }
+
+    #[test]
+    fn test_parse_raw_html() {
+        let doc = parse(
+            r#"doctype html
+body
+  <div class="embed">
+    p Hello
+    <iframe src="https://example.org/"></iframe>
+  </div>
+  p
+"#,
+        )
+        .unwrap();
+
+        let body = doc.children[0].as_element().unwrap();
+
+        let raw = body.children[0].as_raw_html().unwrap();
+        assert_eq!(r#"<div class="embed">"#, raw.data);
+        assert_eq!(2, raw.children.len());
+        assert_eq!("p", raw.children[0].as_element().unwrap().name);
+        let iframe = raw.children[1].as_raw_html().unwrap();
+        assert_eq!(
+            r#"<iframe src="https://example.org/"></iframe>"#,
+            iframe.data
+        );
+
+        let raw = body.children[1].as_raw_html().unwrap();
+        assert_eq!("</div>", raw.data);
+        assert!(raw.children.is_empty());
+
+        assert_eq!("p", body.children[2].as_element().unwrap().name);
+    }
 }
@@ -1,32 +1,730 @@
+use std::borrow::Cow;
+use std::fmt::{self, Write as _};
+use std::io;
+
 use anyhow::Error;
 
-use crate::document::Document;
+use crate::document::{
+    Attr, Comment, CommentKind, ConditionalComment, Document, Element,
+    ElementKind, Node, RawHtml,
+};
+
+/// Output format.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Format {
+    #[default]
+    Html,
+    Xhtml,
+    /// Like xhtml, but any empty element is closed with ` />` too.
+    Xml,
+}
+
+/// Line break style.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Newline {
+    #[default]
+    Lf,
+    CrLf,
+}
+
+impl Newline {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Newline::Lf => "\n",
+            Newline::CrLf => "\r\n",
+        }
+    }
+}
+
+/// Quote for attribute values.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Quote {
+    #[default]
+    Double,
+    Single,
+}
+
+impl Quote {
+    pub fn as_char(&self) -> char {
+        match self {
+            Quote::Double => '"',
+            Quote::Single => '\'',
+        }
+    }
+}
+
+/// Options for rendering.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RenderOptions {
+    /// Output format. If `None`, it's inferred from the doctype (html if the
+    /// document has no doctype).
+    pub format: Option<Format>,
+    /// Omits HTML comments (`/!`) from output. (e.g. for production builds)
+    pub strip_comments: bool,
+    /// Puts block elements on their own lines with indentation.
+    pub pretty: bool,
+    /// Indent string for each level in pretty output.
+    pub indent: String,
+    /// Quote for attribute values.
+    pub attr_quote: Quote,
+    /// Sorts attributes by name.
+    pub sort_attrs: bool,
+    /// Line breaks in pretty output and in multi-line text and comments.
+    pub newline: Newline,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            format: None,
+            strip_comments: false,
+            pretty: false,
+            indent: "  ".to_string(),
+            attr_quote: Quote::Double,
+            sort_attrs: false,
+            newline: Newline::Lf,
+        }
+    }
+}
+
+/// Renderer which keeps options to render documents repeatedly.
+///
+/// ```rust
+/// use lithe::parser::parse;
+/// use lithe::renderer::{Quote, RenderOptions, Renderer};
+///
+/// let renderer = Renderer::new(RenderOptions {
+///     attr_quote: Quote::Single,
+///     sort_attrs: true,
+///     ..Default::default()
+/// });
+/// let doc = parse("a href=\"/\" class=\"nav\" Home").unwrap();
+/// assert_eq!(
+///     "<a class='nav' href='/'>Home</a>",
+///     renderer.render(&doc).unwrap()
+/// );
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Renderer {
+    options: RenderOptions,
+}
+
+impl Renderer {
+    pub fn new(options: RenderOptions) -> Self {
+        Self { options }
+    }
+
+    pub fn options(&self) -> &RenderOptions {
+        &self.options
+    }
+
+    pub fn render(
+        &self,
+        document: &Document,
+    ) -> Result<Markup<'static>, Error> {
+        render_with_options(document, &self.options)
+    }
+
+    /// Writes the document into the `io::Write` (e.g. a file or a socket).
+    ///
+    /// See [`render_to`] about buffering.
+    pub fn render_to<W>(
+        &self,
+        document: &Document,
+        w: &mut W,
+    ) -> Result<(), Error>
+    where
+        W: io::Write,
+    {
+        render_to_with_options(document, w, &self.options)
+    }
+
+    /// Writes the document into the `fmt::Write`.
+    pub fn render_fmt<W>(
+        &self,
+        document: &Document,
+        w: &mut W,
+    ) -> Result<(), Error>
+    where
+        W: fmt::Write,
+    {
+        render_fmt_with_options(document, w, &self.options)
+    }
+}
+
+pub fn render(document: &Document) -> Result<Markup<'static>, Error> {
+    render_with_options(document, &RenderOptions::default())
+}
 
-pub fn render(document: &Document) -> Result<String, Error> {
+pub fn render_with_options(
+    document: &Document,
+    options: &RenderOptions,
+) -> Result<Markup<'static>, Error> {
     let mut result = "".to_string();
+    render_fmt_with_options(document, &mut result, options)?;
+    Ok(Markup(Cow::Owned(result)))
+}
+
+/// Writes the document into the `io::Write` without building a `String`.
+///
+/// Output is written in many small pieces, so an unbuffered writer should be
+/// wrapped in `io::BufWriter`.
+pub fn render_to<W>(document: &Document, w: &mut W) -> Result<(), Error>
+where
+    W: io::Write,
+{
+    render_to_with_options(document, w, &RenderOptions::default())
+}
+
+pub fn render_to_with_options<W>(
+    document: &Document,
+    w: &mut W,
+    options: &RenderOptions,
+) -> Result<(), Error>
+where
+    W: io::Write,
+{
+    let mut adapter = IoAdapter {
+        inner: w,
+        error: None,
+    };
+    match render_fmt_with_options(document, &mut adapter, options) {
+        Ok(()) => Ok(()),
+        Err(e) => Err(adapter.error.map(Error::from).unwrap_or(e)),
+    }
+}
+
+/// Writes the document into the `fmt::Write` without building a `String`.
+pub fn render_fmt<W>(document: &Document, w: &mut W) -> Result<(), Error>
+where
+    W: fmt::Write,
+{
+    render_fmt_with_options(document, w, &RenderOptions::default())
+}
+
+pub fn render_fmt_with_options<W>(
+    document: &Document,
+    w: &mut W,
+    options: &RenderOptions,
+) -> Result<(), Error>
+where
+    W: fmt::Write,
+{
     let document_type = &document.r#type;
+    let format = options
+        .format
+        .or_else(|| document_type.as_ref().map(|t| t.format()));
+    let cx = Context::new(options, format);
+    let out = &mut Output::new(w);
     if let Some(v) = document_type {
-        result.push_str(&v.as_tag());
+        write!(out, "{}", v)?;
+    }
+    for n in &document.children {
+        write_node(out, n, cx, 0)?;
+    }
+    Ok(())
+}
 
-        for e in &document.children {
-            result.push_str(&e.as_tag());
+/// Options resolved for a part of the document.
+#[derive(Clone, Copy)]
+struct Context<'o> {
+    options: &'o RenderOptions,
+    format: Format,
+    /// false in preformatted elements even if `options.pretty` is set
+    pretty: bool,
+}
+
+impl<'o> Context<'o> {
+    fn new(options: &'o RenderOptions, format: Option<Format>) -> Self {
+        Self {
+            options,
+            format: format.unwrap_or_default(),
+            pretty: options.pretty,
+        }
+    }
+}
+
+impl<'o> From<&'o RenderOptions> for Context<'o> {
+    fn from(options: &'o RenderOptions) -> Self {
+        Context::new(options, options.format)
+    }
+}
+
+/// Adapts `io::Write` to `fmt::Write` keeping the original error.
+///
+/// NOTE: Each piece is passed to the writer as it is (no buffering).
+struct IoAdapter<'w, W: io::Write> {
+    inner: &'w mut W,
+    error: Option<io::Error>,
+}
+
+impl<W: io::Write> fmt::Write for IoAdapter<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.inner.write_all(s.as_bytes()).map_err(|e| {
+            self.error = Some(e);
+            fmt::Error
+        })
+    }
+}
+
+/// Sink which knows whether anything has been written.
+struct Output<'w, W: fmt::Write> {
+    inner: &'w mut W,
+    empty: bool,
+}
+
+impl<'w, W: fmt::Write> Output<'w, W> {
+    fn new(inner: &'w mut W) -> Self {
+        Self { inner, empty: true }
+    }
+}
+
+impl<W: fmt::Write> fmt::Write for Output<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if !s.is_empty() {
+            self.empty = false;
+        }
+        self.inner.write_str(s)
+    }
+}
+
+/// Renders a part of a document (for `as_tag`).
+fn to_markup<F>(f: F) -> Markup<'static>
+where
+    F: FnOnce(&mut Output<'_, String>) -> fmt::Result,
+{
+    let mut s = "".to_string();
+    // writing into a String never fails
+    let _ = f(&mut Output::new(&mut s));
+    Markup(Cow::Owned(s))
+}
+
+pub(crate) fn render_element(
+    element: &Element,
+    options: &RenderOptions,
+) -> Markup<'static> {
+    to_markup(|out| write_element(out, element, options.into(), 0))
+}
+
+pub(crate) fn render_comment(
+    comment: &Comment,
+    options: &RenderOptions,
+) -> Markup<'static> {
+    to_markup(|out| write_comment(out, comment, options.into(), 0))
+}
+
+pub(crate) fn render_conditional_comment(
+    comment: &ConditionalComment,
+    options: &RenderOptions,
+) -> Markup<'static> {
+    to_markup(|out| write_conditional_comment(out, comment, options.into(), 0))
+}
+
+pub(crate) fn render_raw_html(
+    raw: &RawHtml,
+    options: &RenderOptions,
+) -> Markup<'static> {
+    to_markup(|out| write_raw_html(out, raw, options.into(), 0))
+}
+
+fn write_node<W: fmt::Write>(
+    out: &mut Output<'_, W>,
+    node: &Node,
+    cx: Context<'_>,
+    level: usize,
+) -> fmt::Result {
+    match node {
+        Node::Element(e) => write_element(out, e, cx, level),
+        Node::Text(t) => write_text(out, &t.data, cx, Escape::Text),
+        Node::Comment(c) => write_comment(out, c, cx, level),
+        Node::ConditionalComment(c) => {
+            write_conditional_comment(out, c, cx, level)
+        }
+        Node::RawHtml(r) => write_raw_html(out, r, cx, level),
+    }
+}
+
+/// Writes a line break and the indentation for the level in pretty output.
+///
+/// Nothing is written at the beginning of output.
+fn write_newline<W: fmt::Write>(
+    out: &mut Output<'_, W>,
+    cx: Context<'_>,
+    level: usize,
+) -> fmt::Result {
+    if !cx.pretty || out.empty {
+        return Ok(());
+    }
+    out.write_str(cx.options.newline.as_str())?;
+    for _ in 0..level {
+        out.write_str(&cx.options.indent)?;
+    }
+    Ok(())
+}
+
+/// Escaping rules.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Escape {
+    /// written as it is (e.g. comments)
+    Never,
+    /// `&`, `<` and `>`
+    Text,
+    /// quotes too, in addition to the text rules
+    Attribute,
+    /// only quotes (for markup in an attribute value)
+    Quotes,
+}
+
+/// Returns the character reference for the character if it needs to be
+/// escaped by the rule.
+fn escaped(c: char, rule: Escape) -> Option<&'static str> {
+    match (c, rule) {
+        (_, Escape::Never) => None,
+        ('"', Escape::Attribute | Escape::Quotes) => Some("&quot;"),
+        ('\'', Escape::Attribute | Escape::Quotes) => Some("&#39;"),
+        (_, Escape::Quotes) => None,
+        ('&', _) => Some("&amp;"),
+        ('<', _) => Some("&lt;"),
+        ('>', _) => Some("&gt;"),
+        _ => None,
+    }
+}
+
+/// Writes the string escaping it by the rule. Line breaks are converted into
+/// the newline style, except in attribute values.
+fn write_text<W: fmt::Write>(
+    out: &mut Output<'_, W>,
+    s: &str,
+    cx: Context<'_>,
+    rule: Escape,
+) -> fmt::Result {
+    let crlf = cx.options.newline == Newline::CrLf && rule != Escape::Attribute;
+    write_escaped(out, s, rule, crlf)
+}
+
+/// Writes the string escaping it by the rule, and converts `\n` into `\r\n`
+/// if `crlf` is set. This is shared by rendering and `escape_text`.
+fn write_escaped<W: fmt::Write>(
+    out: &mut W,
+    s: &str,
+    rule: Escape,
+    crlf: bool,
+) -> fmt::Result {
+    let mut last = 0;
+    for (i, c) in s.char_indices() {
+        let replacement = match escaped(c, rule) {
+            Some(r) => r,
+            None if c == '\n' && crlf => "\r\n",
+            None => continue,
+        };
+        out.write_str(&s[last..i])?;
+        out.write_str(replacement)?;
+        last = i + c.len_utf8();
+    }
+    out.write_str(&s[last..])
+}
+
+/// Returns true if the node starts on a new line in pretty output.
+fn is_block(node: &Node, cx: Context<'_>) -> bool {
+    match node {
+        Node::Element(e) => !e.is_inline(),
+        Node::Text(_) => false,
+        Node::Comment(c) => {
+            c.kind == CommentKind::Html && !cx.options.strip_comments
+        }
+        Node::ConditionalComment(_) | Node::RawHtml(_) => true,
+    }
+}
+
+/// Writes the children, and the closing line break if any of them is a
+/// block. Texts in them are escaped by the rule.
+fn write_children<W: fmt::Write>(
+    out: &mut Output<'_, W>,
+    children: &[Node],
+    cx: Context<'_>,
+    level: usize,
+    rule: Escape,
+) -> fmt::Result {
+    for c in children {
+        match c {
+            Node::Text(t) => write_text(out, &t.data, cx, rule)?,
+            _ => write_node(out, c, cx, level + 1)?,
+        }
+    }
+    if children.iter().any(|c| is_block(c, cx)) {
+        write_newline(out, cx, level)?;
+    }
+    Ok(())
+}
+
+fn write_element<W: fmt::Write>(
+    out: &mut Output<'_, W>,
+    element: &Element,
+    cx: Context<'_>,
+    level: usize,
+) -> fmt::Result {
+    if !element.is_inline() {
+        write_newline(out, cx, level)?;
+    }
+    if element.leading_whitespace {
+        out.write_char(' ')?;
+    }
+    write!(out, "<{}", element.name)?;
+    if cx.options.sort_attrs {
+        let mut attributes = element.attributes.iter().collect::<Vec<_>>();
+        attributes.sort_by_key(|a| a.name);
+        for a in attributes {
+            write_attribute(out, a, cx)?;
+        }
+    } else {
+        for a in &element.attributes {
+            write_attribute(out, a, cx)?;
+        }
+    }
+    if element.self_closing
+        || cx.format == Format::Xml && element.children.is_empty()
+    {
+        out.write_str(" />")?;
+    } else if element.is_void() {
+        // `<br>` in html, and `<br />` in xhtml
+        out.write_str(match cx.format {
+            Format::Html => ">",
+            _ => " />",
+        })?;
+    } else {
+        out.write_char('>')?;
+        // content of pre, script, etc. is never reformatted
+        let children_cx = Context {
+            pretty: cx.pretty && !element.is_preformatted(),
+            ..cx
+        };
+        let rule = match element.kind() {
+            ElementKind::RawText => Escape::Never,
+            _ => Escape::Text,
+        };
+        write_children(out, &element.children, children_cx, level, rule)?;
+        write!(out, "</{}>", element.name)?;
+    }
+    if element.trailing_whitespace {
+        out.write_char(' ')?;
+    }
+    Ok(())
+}
+
+fn write_attribute<W: fmt::Write>(
+    out: &mut Output<'_, W>,
+    attr: &Attr,
+    cx: Context<'_>,
+) -> fmt::Result {
+    let q = cx.options.attr_quote.as_char();
+    match (&attr.value, cx.format) {
+        (Some(v), _) => {
+            write!(out, " {}={}", attr.name, q)?;
+            write_text(out, v, cx, Escape::Attribute)?;
+            out.write_char(q)
+        }
+        // minimized in html, and expanded in xhtml (and xml)
+        (None, Format::Html) => write!(out, " {}", attr.name),
+        (None, _) => write!(out, " {0}={1}{0}{1}", attr.name, q),
+    }
+}
+
+/// Writes `/!` as `<!--...-->`. `/` is never written.
+fn write_comment<W: fmt::Write>(
+    out: &mut Output<'_, W>,
+    comment: &Comment,
+    cx: Context<'_>,
+    level: usize,
+) -> fmt::Result {
+    match comment.kind {
+        CommentKind::Html if !cx.options.strip_comments => {
+            write_newline(out, cx, level)?;
+            out.write_str("<!--")?;
+            write_text(out, &comment.data, cx, Escape::Never)?;
+            out.write_str("-->")
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Writes `/[...]` as `<!--[...]>...<![endif]-->`.
+///
+/// They are not stripped by `strip_comments`, because the children are
+/// content.
+fn write_conditional_comment<W: fmt::Write>(
+    out: &mut Output<'_, W>,
+    comment: &ConditionalComment,
+    cx: Context<'_>,
+    level: usize,
+) -> fmt::Result {
+    write_newline(out, cx, level)?;
+    write!(out, "<!--[{}]>", comment.condition)?;
+    write_children(out, &comment.children, cx, level, Escape::Text)?;
+    out.write_str("<![endif]-->")
+}
+
+/// Writes the raw html line out verbatim followed by its children.
+fn write_raw_html<W: fmt::Write>(
+    out: &mut Output<'_, W>,
+    raw: &RawHtml,
+    cx: Context<'_>,
+    level: usize,
+) -> fmt::Result {
+    write_newline(out, cx, level)?;
+    out.write_str(raw.data)?;
+    for c in &raw.children {
+        write_node(out, c, cx, level + 1)?;
+    }
+    Ok(())
+}
+
+/// HTML which is safe to be written out as it is.
+///
+/// It's made by rendering (e.g. `render` and `as_tag`), by escaping a string
+/// with `escape_text` or `escape_attr`, or by `Markup::trusted` for markup
+/// from a trusted source. Markup given to them
+/// again is not escaped twice (only quotes are escaped for an attribute
+/// value). Once it's turned back into a plain string (e.g. by `as_str`), it's
+/// treated as unescaped text.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Markup<'a>(Cow<'a, str>);
+
+impl<'a> Markup<'a> {
+    /// Wraps markup which is known to be safe without escaping.
+    pub fn trusted<T>(s: T) -> Self
+    where
+        T: Into<Cow<'a, str>>,
+    {
+        Markup(s.into())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn into_string(self) -> String {
+        self.0.into_owned()
+    }
+}
+
+impl fmt::Display for Markup<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl PartialEq<str> for Markup<'_> {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl PartialEq<&str> for Markup<'_> {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
+
+impl PartialEq<String> for Markup<'_> {
+    fn eq(&self, other: &String) -> bool {
+        self.0 == other.as_str()
+    }
+}
+
+impl PartialEq<Markup<'_>> for &str {
+    fn eq(&self, other: &Markup<'_>) -> bool {
+        *self == other.0
+    }
+}
+
+impl PartialEq<Markup<'_>> for String {
+    fn eq(&self, other: &Markup<'_>) -> bool {
+        self.as_str() == other.0
+    }
+}
+
+/// String given to `escape_text` and `escape_attr`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Content<'a> {
+    /// plain text, which is escaped
+    Plain(&'a str),
+    /// already escaped
+    Markup(Markup<'a>),
+}
+
+impl<'a> From<&'a str> for Content<'a> {
+    fn from(s: &'a str) -> Self {
+        Content::Plain(s)
+    }
+}
+
+impl<'a> From<&'a String> for Content<'a> {
+    fn from(s: &'a String) -> Self {
+        Content::Plain(s)
+    }
+}
+
+impl<'a> From<Markup<'a>> for Content<'a> {
+    fn from(m: Markup<'a>) -> Self {
+        Content::Markup(m)
+    }
+}
+
+fn escape(s: &str, rule: Escape) -> Cow<'_, str> {
+    if !s.chars().any(|c| escaped(c, rule).is_some()) {
+        return Cow::Borrowed(s);
+    }
+    let mut out = String::with_capacity(s.len() + 8);
+    // writing into a String never fails
+    let _ = write_escaped(&mut out, s, rule, false);
+    Cow::Owned(out)
+}
+
+/// Escapes `&`, `<` and `>` for HTML text. Markup is returned as it is.
+pub fn escape_text<'a, T>(s: T) -> Markup<'a>
+where
+    T: Into<Content<'a>>,
+{
+    match s.into() {
+        Content::Plain(s) => Markup(escape(s, Escape::Text)),
+        Content::Markup(m) => m,
+    }
+}
+
+/// Escapes `&`, `<`, `>`, `"` and `'` for an attribute value (quoted by
+/// either of quotes). Only quotes are escaped in markup.
+pub fn escape_attr<'a, T>(s: T) -> Markup<'a>
+where
+    T: Into<Content<'a>>,
+{
+    match s.into() {
+        Content::Plain(s) => Markup(escape(s, Escape::Attribute)),
+        Content::Markup(Markup(Cow::Borrowed(s))) => {
+            Markup(escape(s, Escape::Quotes))
+        }
+        Content::Markup(Markup(Cow::Owned(s))) => {
+            match escape(&s, Escape::Quotes) {
+                Cow::Owned(escaped) => Markup(Cow::Owned(escaped)),
+                Cow::Borrowed(_) => Markup(Cow::Owned(s)),
+            }
         }
     }
-    Ok(result)
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::document::{Attr, Element};
+    use crate::document::{Attr, Text};
     use crate::document_type::DocumentType;
+    use crate::dtd::Spec;
+    use crate::parser::parse;
 
     #[test]
     fn test_render_empty_children() {
         let doc = Document::new();
         assert_eq!("".to_string(), render(&doc).unwrap());
 
-        let doc_type = DocumentType::new("html", "5");
+        let doc_type = DocumentType::new(Spec::Html, "5").unwrap();
         let doc = Document {
             r#type: Some(doc_type),
             children: vec![],
@@ -34,6 +732,241 @@ mod test {
         assert_eq!(render(&doc).unwrap(), "<!DOCTYPE HTML>".to_string());
     }
 
+    #[test]
+    fn test_render_xml_prolog() {
+        let doc = Document {
+            r#type: Some(DocumentType::xml(None)),
+            children: vec![Node::Element(Element {
+                name: "feed".to_string(),
+                ..Default::default()
+            })],
+        };
+        assert_eq!(
+            r#"<?xml version="1.0" encoding="utf-8" ?><feed />"#,
+            render(&doc).unwrap()
+        );
+
+        let doc = Document {
+            r#type: Some(DocumentType::xml(Some("ISO-8859-1"))),
+            children: vec![],
+        };
+        assert_eq!(
+            r#"<?xml version="1.0" encoding="ISO-8859-1" ?>"#,
+            render(&doc).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_render_without_doctype() {
+        let p = Element {
+            name: "p".to_string(),
+            children: vec![Node::Text(Text::new("Hi"))],
+            ..Default::default()
+        };
+        let doc = Document {
+            r#type: None,
+            children: vec![
+                Node::Element(p),
+                Node::Element(Element {
+                    name: "br".to_string(),
+                    ..Default::default()
+                }),
+            ],
+        };
+        assert_eq!("<p>Hi</p><br>", render(&doc).unwrap());
+
+        let options = RenderOptions {
+            format: Some(Format::Xhtml),
+            ..Default::default()
+        };
+        assert_eq!(
+            "<p>Hi</p><br />",
+            render_with_options(&doc, &options).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_render_pretty() {
+        let doc = parse(
+            r#"doctype html
+html
+  head
+    title Lithe
+  body
+    /! nav
+    ul#nav
+      li: a href="/" Home
+      li
+        | Read
+        a> href="/docs" docs
+        em please
+    pre: code
+      | fn main() {
+            println!();
+        }
+    script var a = 1;
+    p
+"#,
+        )
+        .unwrap();
+
+        let options = RenderOptions {
+            pretty: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            r#"<!DOCTYPE HTML>
+<html>
+  <head>
+    <title>Lithe</title>
+  </head>
+  <body>
+    <!--nav-->
+    <ul id="nav">
+      <li><a href="/">Home</a></li>
+      <li>Read<a href="/docs">docs</a> <em>please</em></li>
+    </ul>
+    <pre><code>fn main() {
+    println!();
+}</code></pre>
+    <script>var a = 1;</script>
+    <p></p>
+  </body>
+</html>"#,
+            render_with_options(&doc, &options).unwrap()
+        );
+
+        // fragment with tab indentation
+        let doc = parse("div\n  p\n    span a\n").unwrap();
+        let options = RenderOptions {
+            pretty: true,
+            indent: "\t".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            "<div>\n\t<p><span>a</span></p>\n</div>",
+            render_with_options(&doc, &options).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_renderer() {
+        let doc = parse(
+            r#"doctype xml
+entry
+  link(rel="alternate" href="/a")
+  title Lithe
+  summary
+  content type="html"
+    | a
+      b
+"#,
+        )
+        .unwrap();
+
+        let renderer = Renderer::new(RenderOptions {
+            pretty: true,
+            attr_quote: Quote::Single,
+            sort_attrs: true,
+            newline: Newline::CrLf,
+            ..Default::default()
+        });
+        assert_eq!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\" ?>\r\n\
+             <entry>\r\n  \
+             <link href='/a' rel='alternate' />\r\n  \
+             <title>Lithe</title>\r\n  \
+             <summary />\r\n  \
+             <content type='html'>a\r\nb</content>\r\n\
+             </entry>",
+            renderer.render(&doc).unwrap()
+        );
+        // reusable
+        assert_eq!(
+            renderer.render(&doc).unwrap(),
+            renderer.render(&doc).unwrap()
+        );
+
+        let renderer = Renderer::new(RenderOptions {
+            format: Some(Format::Html),
+            strip_comments: true,
+            ..Default::default()
+        });
+        let doc = parse("/! note\ninput type=\"checkbox\" checked\n").unwrap();
+        assert_eq!(
+            r#"<input type="checkbox" checked>"#,
+            renderer.render(&doc).unwrap()
+        );
+        assert!(renderer.options().strip_comments);
+    }
+
+    #[test]
+    fn test_render_to() {
+        let doc = parse("doctype html\np.lead a & b\n").unwrap();
+        let expected = r#"<!DOCTYPE HTML><p class="lead">a &amp; b</p>"#;
+
+        let mut buf: Vec<u8> = vec![];
+        render_to(&doc, &mut buf).unwrap();
+        assert_eq!(expected.as_bytes(), &buf[..]);
+
+        let mut s = "".to_string();
+        render_fmt(&doc, &mut s).unwrap();
+        assert_eq!(expected, s);
+
+        let renderer = Renderer::new(RenderOptions {
+            pretty: true,
+            ..Default::default()
+        });
+        let mut buf: Vec<u8> = vec![];
+        renderer.render_to(&doc, &mut buf).unwrap();
+        assert_eq!(
+            renderer.render(&doc).unwrap().as_str().as_bytes(),
+            &buf[..]
+        );
+    }
+
+    #[test]
+    fn test_render_to_error() {
+        struct Broken;
+
+        impl io::Write for Broken {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::Error::new(io::ErrorKind::BrokenPipe, "closed"))
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let doc = parse("p").unwrap();
+        let err = render_to(&doc, &mut Broken).unwrap_err();
+        let err = err.downcast::<io::Error>().unwrap();
+        assert_eq!(io::ErrorKind::BrokenPipe, err.kind());
+    }
+
+    #[test]
+    fn test_render_escaped_attributes() {
+        let doc =
+            parse(r#"a title="Tom \"&\" Jerry's" href="/?a=1&b=<2>" T&J"#)
+                .unwrap();
+        assert_eq!(
+            r#"<a title="Tom &quot;&amp;&quot; Jerry&#39;s" href="/?a=1&amp;b=&lt;2&gt;">T&amp;J</a>"#,
+            render(&doc).unwrap()
+        );
+
+        let renderer = Renderer::new(RenderOptions {
+            attr_quote: Quote::Single,
+            newline: Newline::CrLf,
+            ..Default::default()
+        });
+        let doc = parse("p title=\"it's\\na\" a\n").unwrap();
+        assert_eq!(
+            "<p title='it&#39;s\na'>a</p>",
+            renderer.render(&doc).unwrap()
+        );
+    }
+
     #[test]
     fn test_render_with_children() {
         let doc = Document::new();
@@ -44,35 +977,38 @@ mod test {
             attributes: vec![
                 Attr {
                     name: "rel",
-                    value: "stylesheet",
+                    value: Some("stylesheet".into()),
                 },
                 Attr {
                     name: "href",
-                    value: "style.css",
+                    value: Some("style.css".into()),
                 },
             ],
             children: vec![],
+            ..Default::default()
         };
 
         let head = Element {
             name: "head".to_string(),
             attributes: vec![],
-            children: vec![link],
+            children: vec![Node::Element(link)],
+            ..Default::default()
         };
 
         let html = Element {
             name: "html".to_string(),
             attributes: vec![Attr {
                 name: "lang",
-                value: "en",
+                value: Some("en".into()),
             }],
-            children: vec![head],
+            children: vec![Node::Element(head)],
+            ..Default::default()
         };
 
-        let doc_type = DocumentType::new("html", "5");
+        let doc_type = DocumentType::new(Spec::Html, "5").unwrap();
         let doc = Document {
             r#type: Some(doc_type),
-            children: vec![html],
+            children: vec![Node::Element(html)],
         };
         assert_eq!(
             render(&doc).unwrap(),
@@ -80,11 +1016,326 @@ mod test {
                 r#"<!DOCTYPE HTML>
 <html lang="en">
 <head>
-<link rel="stylesheet" href="style.css" />
+<link rel="stylesheet" href="style.css">
 </head>
 </html>
 "#
             )
         );
     }
+
+    #[test]
+    fn test_render_raw_text_elements() {
+        let doc = parse(
+            r#"title A & B
+script
+  if (a < b && c) {}
+style p > a {}
+textarea
+  </textarea> & <b>
+pre
+  a < b
+title
+  | Foo
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            inline!(
+                r#"<title>A &amp; B</title>
+<script>if (a < b && c) {}</script>
+<style>p > a {}</style>
+<textarea>&lt;/textarea&gt; &amp; &lt;b&gt;</textarea>
+<pre>a &lt; b</pre>
+<title>Foo</title>
+"#
+            ),
+            render(&doc).unwrap()
+        );
+
+        let doc = parse("pre\n  a\n    | b\n").unwrap();
+        assert_eq!("<pre>a\n  | b</pre>", render(&doc).unwrap());
+    }
+
+    #[test]
+    fn test_render_text() {
+        let p = Element {
+            name: "p".to_string(),
+            attributes: vec![],
+            children: vec![Node::Text(Text::new("Fish & <Chips>"))],
+            ..Default::default()
+        };
+
+        let doc_type = DocumentType::new(Spec::Html, "5").unwrap();
+        let doc = Document {
+            r#type: Some(doc_type),
+            children: vec![Node::Element(p)],
+        };
+        assert_eq!(
+            render(&doc).unwrap(),
+            "<!DOCTYPE HTML><p>Fish &amp; &lt;Chips&gt;</p>"
+        );
+    }
+
+    #[test]
+    fn test_escape() {
+        let m = escape_text("plain");
+        assert!(matches!(m, Markup(Cow::Borrowed("plain"))));
+        assert_eq!("a &amp;&amp; b", escape_text("a && b").as_str());
+        assert_eq!("&lt;script&gt;", escape_text("<script>").as_str());
+        assert_eq!(r#""a's""#, escape_text(r#""a's""#).as_str());
+
+        assert_eq!(
+            "&quot;a&#39;s&quot; &amp; &lt;b&gt;",
+            escape_attr(r#""a's" & <b>"#).as_str()
+        );
+
+        let m = Markup::trusted("<b>bold</b>");
+        assert_eq!("<b>bold</b>", m.to_string());
+        assert_eq!("<b>bold</b>", m.clone().into_string());
+
+        // never escaped twice
+        let m = escape_text("Fish & Chips");
+        assert_eq!(m, escape_text(m.clone()));
+        let doc = parse("p Fish & Chips").unwrap();
+        let m = render(&doc).unwrap();
+        assert_eq!("<p>Fish &amp; Chips</p>", escape_text(m));
+        assert_eq!(
+            "<b title=&quot;x&quot;>&amp;</b>",
+            escape_attr(Markup::trusted(r#"<b title="x">&amp;</b>"#)).as_str()
+        );
+        let s = r#""a" & b"#.to_string();
+        let m = escape_attr(escape_text(&s));
+        assert_eq!("&quot;a&quot; &amp; b", m.as_str());
+    }
+
+    #[test]
+    fn test_render_comments() {
+        let p = Element {
+            name: "p".to_string(),
+            attributes: vec![],
+            children: vec![
+                Node::Comment(Comment::new(CommentKind::Code, "Code")),
+                Node::Comment(Comment::new(CommentKind::Html, "First\nSecond")),
+            ],
+            ..Default::default()
+        };
+
+        let doc_type = DocumentType::new(Spec::Html, "5").unwrap();
+        let doc = Document {
+            r#type: Some(doc_type),
+            children: vec![
+                Node::Comment(Comment::new(CommentKind::Html, "Top")),
+                Node::Element(p),
+            ],
+        };
+        assert_eq!(
+            render(&doc).unwrap(),
+            "<!DOCTYPE HTML><!--Top--><p><!--First\nSecond--></p>"
+        );
+
+        let options = RenderOptions {
+            strip_comments: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            render_with_options(&doc, &options).unwrap(),
+            "<!DOCTYPE HTML><p></p>"
+        );
+    }
+
+    #[test]
+    fn test_render_conditional_comments() {
+        let script = Element {
+            name: "script".to_string(),
+            attributes: vec![Attr {
+                name: "src",
+                value: Some("html5shiv.js".into()),
+            }],
+            children: vec![],
+            ..Default::default()
+        };
+
+        let doc_type = DocumentType::new(Spec::Html, "5").unwrap();
+        let doc = Document {
+            r#type: Some(doc_type),
+            children: vec![Node::ConditionalComment(ConditionalComment {
+                condition: "if lt IE 9",
+                children: vec![Node::Element(script)],
+            })],
+        };
+        let expected = inline!(
+            r#"<!DOCTYPE HTML>
+<!--[if lt IE 9]>
+<script src="html5shiv.js"></script>
+<![endif]-->
+"#
+        );
+        assert_eq!(render(&doc).unwrap(), expected);
+
+        let options = RenderOptions {
+            strip_comments: true,
+            ..Default::default()
+        };
+        assert_eq!(render_with_options(&doc, &options).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_render_boolean_attributes() {
+        let input = Element {
+            name: "input".to_string(),
+            attributes: vec![
+                Attr::new("type", "checkbox"),
+                Attr::boolean("checked"),
+            ],
+            children: vec![],
+            ..Default::default()
+        };
+
+        let doc_type = DocumentType::new(Spec::Html, "5").unwrap();
+        let doc = Document {
+            r#type: Some(doc_type),
+            children: vec![Node::Element(input)],
+        };
+        assert_eq!(
+            render(&doc).unwrap(),
+            r#"<!DOCTYPE HTML><input type="checkbox" checked>"#
+        );
+
+        let options = RenderOptions {
+            format: Some(Format::Xhtml),
+            ..Default::default()
+        };
+        assert_eq!(
+            render_with_options(&doc, &options).unwrap(),
+            r#"<!DOCTYPE HTML><input type="checkbox" checked="checked" />"#
+        );
+    }
+
+    #[test]
+    fn test_render_format_inferred_from_doctype() {
+        let br = || {
+            Node::Element(Element {
+                name: "br".to_string(),
+                ..Default::default()
+            })
+        };
+        let icon = || {
+            Node::Element(Element {
+                name: "my-icon".to_string(),
+                self_closing: true,
+                ..Default::default()
+            })
+        };
+
+        let doc = Document {
+            r#type: Some(DocumentType::new(Spec::Html, "strict").unwrap()),
+            children: vec![br(), icon()],
+        };
+        assert_eq!(
+            inline!(
+                r#"<!DOCTYPE
+ HTML
+ PUBLIC
+ "-//W3C//DTD HTML 4.01//EN"
+ "http://www.w3.org/TR/html4/strict.dtd"
+><br><my-icon />"#
+            ),
+            render(&doc).unwrap()
+        );
+
+        let doc = Document {
+            r#type: Some(DocumentType::new(Spec::Xhtml, "html").unwrap()),
+            children: vec![br(), icon()],
+        };
+        assert_eq!("<!DOCTYPE html><br /><my-icon />", render(&doc).unwrap());
+
+        // explicit format wins
+        let options = RenderOptions {
+            format: Some(Format::Html),
+            ..Default::default()
+        };
+        assert_eq!(
+            "<!DOCTYPE html><br><my-icon />",
+            render_with_options(&doc, &options).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_render_whitespace_control() {
+        let a = Element {
+            name: "a".to_string(),
+            attributes: vec![Attr::new("href", "/")],
+            children: vec![Node::Text(Text::new("Home"))],
+            trailing_whitespace: true,
+            ..Default::default()
+        };
+        let button = Element {
+            name: "button".to_string(),
+            children: vec![Node::Text(Text::new("OK"))],
+            leading_whitespace: true,
+            trailing_whitespace: true,
+            ..Default::default()
+        };
+        let img = Element {
+            name: "img".to_string(),
+            leading_whitespace: true,
+            ..Default::default()
+        };
+        let p = Element {
+            name: "p".to_string(),
+            children: vec![
+                Node::Element(a),
+                Node::Element(button),
+                Node::Element(img),
+            ],
+            ..Default::default()
+        };
+
+        let doc_type = DocumentType::new(Spec::Html, "5").unwrap();
+        let doc = Document {
+            r#type: Some(doc_type),
+            children: vec![Node::Element(p)],
+        };
+        assert_eq!(
+            render(&doc).unwrap(),
+            inline!(
+                r#"<!DOCTYPE HTML>
+<p>
+<a href="/">Home</a> 
+ <button>OK</button> 
+ <img>
+</p>
+"#
+            )
+        );
+    }
+
+    #[test]
+    fn test_render_raw_html() {
+        let p = Element {
+            name: "p".to_string(),
+            children: vec![Node::Text(Text::new("Hello"))],
+            ..Default::default()
+        };
+
+        let doc_type = DocumentType::new(Spec::Html, "5").unwrap();
+        let doc = Document {
+            r#type: Some(doc_type),
+            children: vec![
+                Node::RawHtml(RawHtml {
+                    data: r#"<div id="embed" data-x="<&>">"#,
+                    children: vec![Node::Element(p)],
+                }),
+                Node::RawHtml(RawHtml {
+                    data: "</div>",
+                    children: vec![],
+                }),
+            ],
+        };
+        assert_eq!(
+            render(&doc).unwrap(),
+            r#"<!DOCTYPE HTML><div id="embed" data-x="<&>"><p>Hello</p></div>"#
+        );
+    }
 }